@@ -0,0 +1,104 @@
+// Cost of what the editor does with growing layouts: the lookups of every frame, and edits run
+// through the history followed by a cache sync, as the canvas does after each change. All of
+// these should stay flat from a thousand to a hundred thousand blocks.
+//
+//     cargo bench -p railway_layout
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use railway_layout::{BlockKind, Command, Direction, GroupCache, History, Layout};
+
+const SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+// Long runs, so edits at their ends pay for whatever scales with the size of a group
+const RUN_LENGTH: i32 = 1_000;
+const ITERATIONS: u32 = 20_000;
+const EDITS: u32 = 2_000;
+
+// Horizontal runs of `RUN_LENGTH` blocks on every other row, laid through the history.
+// Returns the average time per run.
+fn build_layout(blocks: usize, layout: &mut Layout, history: &mut History) -> Duration {
+    let rows = (blocks as i32 / RUN_LENGTH).max(1);
+    let start = Instant::now();
+    for row in 0..rows {
+        let run = Command::LayRun { start: (0, row * 2), end: (RUN_LENGTH - 1, row * 2) };
+        history.execute(layout, run).expect("rows do not overlap");
+    }
+    start.elapsed() / rows as u32
+}
+
+// Cells spread over the layout and the empty rows between its runs
+fn probe_cells(layout: &Layout) -> Vec<(i32, i32)> {
+    let rows = (layout.block_count() as i32 / RUN_LENGTH).max(1) * 2;
+    (0..ITERATIONS as i32)
+        .map(|i| ((i * 37) % (RUN_LENGTH + 2) - 1, (i * 53) % (rows + 2) - 1))
+        .collect()
+}
+
+fn time(iterations: u32, mut f: impl FnMut(u32)) -> Duration {
+    let start = Instant::now();
+    for i in 0..iterations {
+        f(i);
+    }
+    start.elapsed() / iterations
+}
+
+fn main() {
+    println!(
+        "{:>8}  {:>12}  {:>12}  {:>12}  {:>12}  {:>12}  {:>12}  {:>12}",
+        "blocks", "block_at", "auto_kind", "frame", "lay run", "place", "undo", "redo",
+    );
+    for size in SIZES {
+        let mut layout = Layout::default();
+        let mut history = History::default();
+        let lay_run = build_layout(size, &mut layout, &mut history);
+        // Stands in for the canvas and minimap caches, which keep something per group
+        let mut cache: GroupCache<usize> = GroupCache::default();
+        cache.sync(&layout, &history, |group| group.blocks.len());
+
+        let cells = probe_cells(&layout);
+        let cell = |i: u32| cells[i as usize % cells.len()];
+
+        let block_at = time(ITERATIONS, |i| {
+            black_box(layout.block_at(cell(i)));
+        });
+        let auto_kind = time(ITERATIONS, |i| {
+            black_box(layout.auto_kind(cell(i)));
+        });
+        // What a frame asks of the layout: the block under the pointer, the placement preview
+        // and the caches catching up
+        let frame = time(ITERATIONS, |i| {
+            black_box(layout.block_at(cell(i)));
+            black_box(layout.is_occupied(cell(i)));
+            black_box(layout.auto_kind(cell(i)));
+            black_box(cache.sync(&layout, &history, |group| group.blocks.len()));
+        });
+
+        // A click extending the first run by one block, undone and redone, each followed by a
+        // cache sync. Undoing every other edit keeps the run from growing.
+        let (mut place, mut undo, mut redo) = (Duration::ZERO, Duration::ZERO, Duration::ZERO);
+        let kind = BlockKind::Straight(Direction::Horizontal);
+        for _ in 0..EDITS {
+            let start = Instant::now();
+            let command = Command::PlaceBlock { grid_pos: (RUN_LENGTH, 0), kind: Some(kind) };
+            history.execute(&mut layout, command).expect("cell is free");
+            cache.sync(&layout, &history, |group| group.blocks.len());
+            place += start.elapsed();
+
+            let start = Instant::now();
+            history.undo(&mut layout);
+            cache.sync(&layout, &history, |group| group.blocks.len());
+            undo += start.elapsed();
+
+            let start = Instant::now();
+            history.redo(&mut layout);
+            cache.sync(&layout, &history, |group| group.blocks.len());
+            redo += start.elapsed();
+            history.undo(&mut layout);
+        }
+
+        println!(
+            "{:>8}  {:>12?}  {:>12?}  {:>12?}  {:>12?}  {:>12?}  {:>12?}  {:>12?}",
+            layout.block_count(), block_at, auto_kind, frame, lay_run, place / EDITS, undo / EDITS, redo / EDITS,
+        );
+    }
+}
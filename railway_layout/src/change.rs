@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+use crate::layout::Layout;
+use crate::models::{Connection, Group, GroupId, Turnout};
+use crate::table::Journal;
+
+// MARK: - Change
+// What one edit touched, as it was on one side of the edit: every group, connection and turnout
+// it changed (`None` where there was none) and the id counters. History steps keep these instead
+// of copies of the layout, so recording an edit costs about as much as the edit itself.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Change {
+    groups: HashMap<GroupId, Option<Group>>,
+    connections: Journal<Connection>,
+    turnouts: Journal<Turnout>,
+    counters: [u32; 4],
+}
+
+impl Change {
+    pub(crate) fn groups(&self) -> impl Iterator<Item = GroupId> + '_ {
+        self.groups.keys().copied()
+    }
+}
+
+// MARK: - Recording
+impl Layout {
+    // Start recording what gets changed, until `end_change`
+    pub(crate) fn begin_change(&mut self) {
+        self.journal = Some(Change { counters: self.counters(), ..Change::default() });
+        self.connections.begin_journal();
+        self.turnouts.begin_journal();
+    }
+
+    // Stop recording. The result puts the layout back as it was when recording began.
+    pub(crate) fn end_change(&mut self) -> Change {
+        let mut change = self.journal.take().unwrap_or_default();
+        change.connections = self.connections.end_journal();
+        change.turnouts = self.turnouts.end_journal();
+        change
+    }
+
+    // Note a group as it is, before it is changed, added or removed. Every edit of `groups`
+    // must call this first for edits to be undone.
+    pub(crate) fn journal_group(&mut self, group_id: GroupId) {
+        if let Some(change) = &mut self.journal {
+            change.groups.entry(group_id).or_insert_with(|| self.groups.get(&group_id).cloned());
+        }
+    }
+
+    // Swap in everything the change holds. Returns what was there instead, which undoes this again.
+    pub(crate) fn apply_change(&mut self, change: Change) -> Change {
+        for group in change.groups.keys().filter_map(|id| self.groups.get(id)) {
+            for block in &group.blocks {
+                self.index.remove_block(block.id, block.grid_pos);
+            }
+        }
+        let mut groups = HashMap::with_capacity(change.groups.len());
+        for (id, group) in change.groups {
+            let current = match group {
+                Some(group) => self.groups.insert(id, group),
+                None => self.groups.remove(&id),
+            };
+            groups.insert(id, current);
+        }
+        for id in groups.keys() {
+            self.reindex(*id);
+        }
+
+        let counters = self.counters();
+        [self.next_block_id, self.next_group_id, self.next_turnout_id, self.next_connection_id] = change.counters;
+        Change {
+            groups,
+            connections: self.connections.restore(change.connections),
+            turnouts: self.turnouts.restore(change.turnouts),
+            counters,
+        }
+    }
+
+    fn counters(&self) -> [u32; 4] {
+        [self.next_block_id, self.next_group_id, self.next_turnout_id, self.next_connection_id]
+    }
+}
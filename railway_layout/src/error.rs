@@ -0,0 +1,75 @@
+use std::fmt;
+use crate::models::{BlockId, ConnectionId, Endpoint, GroupId, TurnoutId};
+
+// MARK: - LayoutError
+#[derive(Debug)]
+pub enum LayoutError {
+    GroupNotFound(GroupId),
+    BlockNotFound(BlockId),
+    PositionOccupied((i32, i32)),
+    SameGroup(GroupId),
+    NotLinked(GroupId, GroupId),
+    NotAligned { group_id: GroupId, grid_pos: (i32, i32) },
+    NotAnEndpoint(BlockId),
+    CannotSplit(BlockId),
+    ConnectionExists,
+    TurnoutNotFound(TurnoutId),
+    ConnectionNotFound(ConnectionId),
+    DuplicateEndpoint,
+    EndpointInUse(Endpoint),
+    // The file's format version is newer than this build understands
+    NewerFormat { found: u32, supported: u32 },
+    Io(std::io::Error),
+    Serde(serde_json::Error),
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutError::GroupNotFound(id) => write!(f, "Group {} does not exist", id),
+            LayoutError::BlockNotFound(id) => write!(f, "Block {} does not exist", id),
+            LayoutError::PositionOccupied(pos) => write!(f, "Position {:?} is already occupied", pos),
+            LayoutError::SameGroup(id) => write!(f, "Cannot merge group {} with itself", id),
+            LayoutError::NotLinked(a, b) => write!(f, "Groups {} and {} do not meet end to end", a, b),
+            LayoutError::NotAligned { group_id, grid_pos } => {
+                write!(f, "Track at {:?} does not meet an open end of group {}", grid_pos, group_id)
+            }
+            LayoutError::NotAnEndpoint(id) => write!(f, "Block {} is not the start or end of its group", id),
+            LayoutError::CannotSplit(id) => write!(f, "Block {} is the only block of its group", id),
+            LayoutError::ConnectionExists => write!(f, "Given connection already exists"),
+            LayoutError::TurnoutNotFound(id) => write!(f, "Turnout {} does not exist", id),
+            LayoutError::ConnectionNotFound(id) => write!(f, "Connection {} does not exist", id),
+            LayoutError::DuplicateEndpoint => write!(f, "The same endpoint cannot be used twice"),
+            LayoutError::EndpointInUse(endpoint) => write!(f, "The {} is already in use", endpoint),
+            LayoutError::NewerFormat { found, supported } => write!(
+                f,
+                "This layout was written by a newer version of the editor (format {}, this version supports up to {})",
+                found, supported
+            ),
+            LayoutError::Io(e) => write!(f, "I/O error: {}", e),
+            LayoutError::Serde(e) => write!(f, "Invalid layout data: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LayoutError::Io(e) => Some(e),
+            LayoutError::Serde(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for LayoutError {
+    fn from(e: std::io::Error) -> Self {
+        LayoutError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for LayoutError {
+    fn from(e: serde_json::Error) -> Self {
+        LayoutError::Serde(e)
+    }
+}
@@ -0,0 +1,125 @@
+use serde::de::Error as _;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use crate::error::LayoutError;
+use crate::layout::Layout;
+
+// Layout files carry a top-level `format_version`. Older documents are upgraded one version
+// at a time by the migrations below before they are deserialized into a `Layout`.
+//
+// History:
+// 0 - no `format_version` field. Written before versioning; depending on its age a file
+//     may lack block kinds, keep connections inside each group and store turnouts as a map.
+// 1 - connections in a layout-level list with ids and a `directed` flag, turnouts as a list.
+// 2 - groups may carry an optional `name` and a `reversed` flag; both are left out when unset.
+//     Readers of version 1 would drop `reversed` and swap the ends of reversed groups.
+
+pub const CURRENT_FORMAT_VERSION: u32 = 2;
+
+type Migration = fn(&mut Map<String, Value>);
+
+// `MIGRATIONS[n]` upgrades a version `n` document to version `n + 1`
+const MIGRATIONS: [Migration; CURRENT_FORMAT_VERSION as usize] = [migrate_v0_to_v1, migrate_v1_to_v2];
+
+#[derive(Serialize)]
+struct Document<'a> {
+    format_version: u32,
+    #[serde(flatten)]
+    layout: &'a Layout,
+}
+
+pub(crate) fn to_document(layout: &Layout) -> Result<String, LayoutError> {
+    let document = Document { format_version: CURRENT_FORMAT_VERSION, layout };
+    Ok(serde_json::to_string_pretty(&document)?)
+}
+
+pub(crate) fn from_document(contents: &str) -> Result<Layout, LayoutError> {
+    let value: Value = serde_json::from_str(contents)?;
+    let Value::Object(mut document) = value else {
+        return Err(serde_json::Error::custom("a layout must be a JSON object").into());
+    };
+
+    let version = match document.remove("format_version") {
+        None => 0,
+        Some(version) => version.as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| serde_json::Error::custom("format_version must be a whole number"))?,
+    };
+    if version > CURRENT_FORMAT_VERSION {
+        return Err(LayoutError::NewerFormat { found: version, supported: CURRENT_FORMAT_VERSION });
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(&mut document);
+    }
+    Ok(serde_json::from_value(Value::Object(document))?)
+}
+
+// MARK: - Migrations
+fn migrate_v0_to_v1(document: &mut Map<String, Value>) {
+    // The editor state used to be saved along with the layout
+    document.remove("app_mode");
+
+    let mut connections = vec![];
+    let mut next_connection_id = 1;
+    if let Some(groups) = document.get_mut("groups").and_then(Value::as_object_mut) {
+        for group in groups.values_mut().filter_map(Value::as_object_mut) {
+            infer_block_kinds(group);
+
+            let Some(Value::Array(legacy)) = group.remove("connections") else {
+                continue;
+            };
+            for conn in legacy {
+                let (Some(from), Some(to)) = (
+                    legacy_endpoint(&conn, "from_group", "from_connection_type"),
+                    legacy_endpoint(&conn, "to_group", "to_connection_type"),
+                ) else {
+                    // `Unknown` ends never pointed anywhere
+                    continue;
+                };
+                let duplicate = connections.iter().any(|existing: &Value| existing["from"] == from && existing["to"] == to);
+                if !duplicate {
+                    connections.push(json!({ "id": next_connection_id, "from": from, "to": to, "directed": true }));
+                    next_connection_id += 1;
+                }
+            }
+        }
+    }
+    document.insert("connections".to_string(), Value::Array(connections));
+    document.insert("next_connection_id".to_string(), json!(next_connection_id));
+
+    // Turnouts were keyed by id
+    if let Some(Value::Object(turnouts)) = document.remove("turnouts") {
+        let turnouts: Vec<Value> = turnouts.into_iter().map(|(_, turnout)| turnout).collect();
+        document.insert("turnouts".to_string(), Value::Array(turnouts));
+    }
+}
+
+// Groups had neither a name nor a reversed start and end
+fn migrate_v1_to_v2(document: &mut Map<String, Value>) {
+    if let Some(groups) = document.get_mut("groups").and_then(Value::as_object_mut) {
+        for group in groups.values_mut().filter_map(Value::as_object_mut) {
+            group.entry("name").or_insert(Value::Null);
+            group.entry("reversed").or_insert(json!(false));
+        }
+    }
+}
+
+// Blocks saved before track kinds existed are straights along their group
+fn infer_block_kinds(group: &mut Map<String, Value>) {
+    let direction = match group.get("direction").and_then(Value::as_str) {
+        Some("Vertical") => "Vertical",
+        _ => "Horizontal",
+    };
+    if let Some(Value::Array(blocks)) = group.get_mut("blocks") {
+        for block in blocks.iter_mut().filter_map(Value::as_object_mut) {
+            block.entry("kind").or_insert_with(|| json!({ "Straight": direction }));
+        }
+    }
+}
+
+fn legacy_endpoint(conn: &Value, group_key: &str, end_key: &str) -> Option<Value> {
+    let group = conn.get(group_key)?.as_u64()?;
+    let end = conn.get(end_key)?.as_str()?;
+    matches!(end, "Start" | "End").then(|| json!({ "group": group, "end": end }))
+}
@@ -0,0 +1,286 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use crate::error::LayoutError;
+use crate::format::CURRENT_FORMAT_VERSION;
+use crate::layout::Layout;
+use crate::models::{BlockId, Connection, ConnectionId, ConnectionType, Endpoint, Group, GroupId, Turnout, TurnoutId};
+use crate::table::{Attached, EndpointTable};
+
+// MARK: - Fragment
+// A self-contained piece of a layout: some blocks with their groups and the connections and
+// turnouts among them. Positions are relative to the top-left corner of the fragment and
+// ids are only meaningful inside it; pasting assigns fresh ones.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fragment {
+    pub format_version: u32,
+    pub groups: Vec<Group>,
+    pub connections: Vec<Connection>,
+    pub turnouts: Vec<Turnout>,
+}
+
+impl Fragment {
+    pub fn is_empty(&self) -> bool {
+        self.groups.iter().all(|group| group.blocks.is_empty())
+    }
+
+    // Width and height in grid cells
+    pub fn size(&self) -> (i32, i32) {
+        let positions = self.groups.iter().flat_map(|group| group.blocks.iter().map(|block| block.grid_pos));
+        positions.fold((0, 0), |(w, h), (x, y)| (w.max(x + 1), h.max(y + 1)))
+    }
+
+    pub fn to_json(&self) -> Result<String, LayoutError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    // Fragments are written in the current format only; one from a newer editor is refused
+    pub fn from_json(contents: &str) -> Result<Fragment, LayoutError> {
+        let fragment: Fragment = serde_json::from_str(contents)?;
+        if fragment.format_version > CURRENT_FORMAT_VERSION {
+            return Err(LayoutError::NewerFormat { found: fragment.format_version, supported: CURRENT_FORMAT_VERSION });
+        }
+        Ok(fragment)
+    }
+}
+
+// MARK: - Pasted
+// What `paste_fragment` added. Connections and turnouts of the fragment that could not be
+// added, because an endpoint was already taken or its group is missing, are handed back.
+#[derive(Debug, Clone, Default)]
+pub struct Pasted {
+    pub blocks: Vec<BlockId>,
+    pub skipped_connections: Vec<Connection>,
+    pub skipped_turnouts: Vec<Turnout>,
+}
+
+// MARK: - Copy and paste
+impl Layout {
+    // Copy the given blocks into a fragment. Groups are cut down to the copied blocks, and
+    // only connections and turnouts whose endpoints all remain group ends come along.
+    pub fn copy_fragment(&self, block_ids: &[BlockId]) -> Result<Fragment, LayoutError> {
+        let selected: HashSet<BlockId> = block_ids.iter().copied().collect();
+        let mut group_ids = HashSet::new();
+        for id in &selected {
+            let group = self.group_of_block(*id).ok_or(LayoutError::BlockNotFound(*id))?;
+            group_ids.insert(group.id);
+        }
+
+        // Only the groups holding copied blocks are taken, with what is attached among them.
+        // Removing their other blocks splits them and drops dangling connections the usual way.
+        let mut copy = Layout {
+            groups: group_ids.iter().map(|id| (*id, self.groups[id].clone())).collect(),
+            connections: attached_within(&self.connections, &group_ids)?,
+            turnouts: attached_within(&self.turnouts, &group_ids)?,
+            ..Layout::default()
+        };
+        copy.ensure_id_counters();
+        copy.rebuild_index();
+        let others: Vec<BlockId> = copy.groups.values()
+            .flat_map(|group| group.blocks.iter().map(|block| block.id))
+            .filter(|id| !selected.contains(id))
+            .collect();
+        copy.remove_blocks(&others)?;
+
+        let mut groups: Vec<Group> = copy.groups.into_values().collect();
+        groups.sort_by_key(|group| group.id);
+        let min_x = groups.iter().flat_map(|group| group.blocks.iter()).map(|block| block.grid_pos.0).min().unwrap_or(0);
+        let min_y = groups.iter().flat_map(|group| group.blocks.iter()).map(|block| block.grid_pos.1).min().unwrap_or(0);
+        for block in groups.iter_mut().flat_map(|group| group.blocks.iter_mut()) {
+            block.grid_pos = (block.grid_pos.0 - min_x, block.grid_pos.1 - min_y);
+        }
+
+        Ok(Fragment {
+            format_version: CURRENT_FORMAT_VERSION,
+            groups,
+            connections: copy.connections.iter().cloned().collect(),
+            turnouts: copy.turnouts.iter().cloned().collect(),
+        })
+    }
+
+    // Paste a fragment with its top-left corner at `origin`, giving everything fresh ids.
+    // Pasted track that meets an open end is merged into that group.
+    pub fn paste_fragment(&mut self, fragment: &Fragment, origin: (i32, i32)) -> Result<Pasted, LayoutError> {
+        let mut targets = HashSet::new();
+        for block in fragment.groups.iter().flat_map(|group| group.blocks.iter()) {
+            let target = (block.grid_pos.0 + origin.0, block.grid_pos.1 + origin.1);
+            if !targets.insert(target) || self.is_occupied(target) {
+                return Err(LayoutError::PositionOccupied(target));
+            }
+        }
+
+        let mut group_ids: HashMap<GroupId, GroupId> = HashMap::new();
+        let mut pasted = Pasted::default();
+        for source in fragment.groups.iter().filter(|group| !group.blocks.is_empty()) {
+            let mut group = source.clone();
+            group.id = self.alloc_group_id();
+            group_ids.insert(source.id, group.id);
+
+            let mut block_ids = HashMap::new();
+            for block in &mut group.blocks {
+                let new_id = self.alloc_block_id();
+                block_ids.insert(block.id, new_id);
+                block.id = new_id;
+                block.grid_pos = (block.grid_pos.0 + origin.0, block.grid_pos.1 + origin.1);
+                pasted.blocks.push(new_id);
+            }
+            // Keep the same ends so the copied connections still fit
+            group.start_block_id = group.start_block_id.and_then(|id| block_ids.get(&id).copied());
+            group.end_block_id = group.end_block_id.and_then(|id| block_ids.get(&id).copied());
+            if group.start_block_id.is_none() || group.end_block_id.is_none() {
+                group.update_start_end_blocks();
+            }
+            group.update_direction();
+            let group_id = group.id;
+            self.journal_group(group_id);
+            self.groups.insert(group_id, group);
+            self.reindex(group_id);
+        }
+
+        let remap = |endpoint: Endpoint| group_ids.get(&endpoint.group).map(|group| Endpoint { group: *group, ..endpoint });
+        for source in &fragment.connections {
+            let added = match (remap(source.from), remap(source.to)) {
+                (Some(from), Some(to)) => {
+                    let id = ConnectionId(self.next_connection_id);
+                    self.connections.insert(Connection { id, from, to, directed: source.directed }).is_ok()
+                }
+                _ => false,
+            };
+            if added {
+                self.next_connection_id += 1;
+            } else {
+                pasted.skipped_connections.push(source.clone());
+            }
+        }
+        for source in &fragment.turnouts {
+            let added = match (remap(source.points), remap(source.normal), remap(source.reverse)) {
+                (Some(points), Some(normal), Some(reverse)) => {
+                    let id = TurnoutId(self.next_turnout_id);
+                    self.turnouts.insert(Turnout { id, points, normal, reverse, position: source.position }).is_ok()
+                }
+                _ => false,
+            };
+            if added {
+                self.next_turnout_id += 1;
+            } else {
+                pasted.skipped_turnouts.push(source.clone());
+            }
+        }
+
+        let mut new_groups: Vec<GroupId> = group_ids.into_values().collect();
+        new_groups.sort();
+        for group_id in new_groups {
+            self.merge_with_neighbors(group_id);
+        }
+        Ok(pasted)
+    }
+}
+
+// The records of a table attached to the given groups whose endpoints all lie on those groups
+fn attached_within<T: Attached + Clone>(table: &EndpointTable<T>, group_ids: &HashSet<GroupId>) -> Result<EndpointTable<T>, LayoutError> {
+    let mut within = EndpointTable::default();
+    for group in group_ids {
+        for end in [ConnectionType::Start, ConnectionType::End] {
+            let Some(record) = table.at(Endpoint { group: *group, end }) else {
+                continue;
+            };
+            let inside = record.endpoints().iter().all(|endpoint| group_ids.contains(&endpoint.group));
+            if inside && within.get(record.id()).is_none() {
+                within.insert(record.clone())?;
+            }
+        }
+    }
+    Ok(within)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pasted_copy_gets_fresh_ids_and_keeps_its_shape() {
+        let mut layout = Layout::default();
+        let run = layout.lay_run((0, 0), (2, 0)).unwrap();
+        let other = layout.lay_run((4, 0), (4, 2)).unwrap();
+        layout.connect_groups(run[2], other[0], true).unwrap();
+        let copied: Vec<BlockId> = run.iter().chain(&other).copied().collect();
+
+        let fragment = Fragment::from_json(&layout.copy_fragment(&copied).unwrap().to_json().unwrap()).unwrap();
+        assert_eq!(fragment.size(), (5, 3));
+        let pasted = layout.paste_fragment(&fragment, (0, 10)).unwrap();
+        assert!(pasted.skipped_connections.is_empty() && pasted.skipped_turnouts.is_empty());
+        let pasted = pasted.blocks;
+
+        assert_eq!(pasted.len(), copied.len());
+        assert!(pasted.iter().all(|id| !copied.contains(id)));
+        for (old, new) in copied.iter().zip(&pasted) {
+            let (old, new) = (layout.block(*old).unwrap(), layout.block(*new).unwrap());
+            assert_eq!(new.grid_pos, (old.grid_pos.0, old.grid_pos.1 + 10));
+            assert_eq!(new.kind, old.kind);
+        }
+        assert_eq!(layout.groups.len(), 4);
+        assert_eq!(layout.connections.len(), 2);
+        let connection = layout.connections.at(layout.endpoint_of_block(pasted[2]).unwrap()).unwrap();
+        assert_eq!(connection.to, layout.endpoint_of_block(pasted[3]).unwrap());
+        assert!(connection.directed);
+        assert!(layout.validate().is_empty());
+    }
+
+    #[test]
+    fn connections_to_blocks_left_behind_are_not_copied() {
+        let mut layout = Layout::default();
+        let run = layout.lay_run((0, 0), (2, 0)).unwrap();
+        let other = layout.lay_run((4, 0), (4, 2)).unwrap();
+        layout.connect_groups(run[2], other[0], false).unwrap();
+
+        let fragment = layout.copy_fragment(&run).unwrap();
+        assert_eq!(fragment.groups.len(), 1);
+        assert!(fragment.connections.is_empty());
+    }
+
+    #[test]
+    fn only_what_is_attached_among_the_copied_ends_comes_along() {
+        let mut layout = Layout::default();
+        let main = layout.lay_run((0, 0), (4, 0)).unwrap();
+        let branch = layout.lay_run((6, 0), (8, 0)).unwrap();
+        let siding = layout.lay_run((6, 2), (8, 2)).unwrap();
+        layout.lay_run((0, 4), (4, 4)).unwrap();
+        layout.connect_groups(main[0], siding[2], false).unwrap();
+        layout.add_turnout(main[4], branch[0], siding[0]).unwrap();
+
+        let whole = layout.copy_fragment(&[main.clone(), branch, siding.clone()].concat()).unwrap();
+        assert_eq!((whole.groups.len(), whole.connections.len(), whole.turnouts.len()), (3, 1, 1));
+
+        // Leaving the end of the main line behind leaves its turnout behind too
+        let part = layout.copy_fragment(&[&main[..3], &siding[..]].concat()).unwrap();
+        assert_eq!((part.groups.len(), part.connections.len(), part.turnouts.len()), (2, 1, 0));
+    }
+
+    #[test]
+    fn connections_that_cannot_be_pasted_are_handed_back() {
+        let mut layout = Layout::default();
+        let run = layout.lay_run((0, 0), (2, 0)).unwrap();
+        let other = layout.lay_run((4, 0), (4, 2)).unwrap();
+        layout.connect_groups(run[2], other[0], false).unwrap();
+        let mut fragment = layout.copy_fragment(&[run, other].concat()).unwrap();
+        // A second connection claiming the same endpoints cannot be added
+        let duplicate = Connection { id: ConnectionId(99), ..fragment.connections[0].clone() };
+        fragment.connections.push(duplicate.clone());
+
+        let pasted = layout.paste_fragment(&fragment, (0, 10)).unwrap();
+        assert_eq!(pasted.blocks.len(), 6);
+        assert_eq!(pasted.skipped_connections, vec![duplicate]);
+        assert!(pasted.skipped_turnouts.is_empty());
+        assert_eq!(layout.connections.len(), 2);
+    }
+
+    #[test]
+    fn pasting_onto_track_changes_nothing() {
+        let mut layout = Layout::default();
+        let run = layout.lay_run((0, 0), (2, 0)).unwrap();
+        let fragment = layout.copy_fragment(&run).unwrap();
+
+        assert!(matches!(layout.paste_fragment(&fragment, (2, 0)), Err(LayoutError::PositionOccupied((2, 0)))));
+        assert_eq!(layout.block_count(), 3);
+        assert_eq!(layout.groups.len(), 1);
+    }
+}
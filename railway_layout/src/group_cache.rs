@@ -0,0 +1,82 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use crate::history::History;
+use crate::layout::Layout;
+use crate::models::{Group, GroupId};
+
+// MARK: - GroupCache
+// Something derived from each group of the layout, e.g. the shapes to draw it with. The layout
+// is only looked at again when the history revision moves on, and then only the groups the
+// history reports as changed are fingerprinted, and rebuilt if their contents differ.
+#[derive(Debug)]
+pub struct GroupCache<T> {
+    revision: Option<u64>,
+    entries: HashMap<GroupId, (u64, T)>,
+}
+
+impl<T> Default for GroupCache<T> {
+    fn default() -> Self {
+        Self {
+            revision: None,
+            entries: HashMap::new(),
+        }
+    }
+}
+
+impl<T> GroupCache<T> {
+    // Bring the cache up to date with a layout edited through `history`.
+    // Returns true when any entry was rebuilt or dropped.
+    pub fn sync(&mut self, layout: &Layout, history: &History, mut build: impl FnMut(&Group) -> T) -> bool {
+        let revision = history.revision();
+        if self.revision == Some(revision) {
+            return false;
+        }
+        let changed_groups = self.revision.and_then(|since| history.changed_groups_since(since));
+        self.revision = Some(revision);
+
+        // Without a record of what changed every group is looked at
+        let Some(changed_groups) = changed_groups else {
+            let before = self.entries.len();
+            self.entries.retain(|id, _| layout.groups.contains_key(id));
+            let mut changed = self.entries.len() != before;
+            for group in layout.groups.values() {
+                changed |= self.refresh(group.id, Some(group), &mut build);
+            }
+            return changed;
+        };
+
+        let mut changed = false;
+        for group_id in changed_groups {
+            changed |= self.refresh(group_id, layout.groups.get(&group_id), &mut build);
+        }
+        changed
+    }
+
+    // Rebuild the entry of one group if its contents changed, or drop it if the group is gone
+    fn refresh(&mut self, group_id: GroupId, group: Option<&Group>, build: &mut impl FnMut(&Group) -> T) -> bool {
+        let Some(group) = group else {
+            return self.entries.remove(&group_id).is_some();
+        };
+        let fingerprint = fingerprint(group);
+        if self.entries.get(&group_id).is_some_and(|(cached, _)| *cached == fingerprint) {
+            return false;
+        }
+        self.entries.insert(group_id, (fingerprint, build(group)));
+        true
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.entries.values().map(|(_, value)| value)
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (GroupId, &mut T)> {
+        self.entries.iter_mut().map(|(id, (_, value))| (*id, value))
+    }
+}
+
+fn fingerprint(group: &Group) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    group.hash(&mut hasher);
+    hasher.finish()
+}
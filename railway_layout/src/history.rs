@@ -0,0 +1,398 @@
+use std::collections::{HashSet, VecDeque};
+use crate::change::Change;
+use crate::error::LayoutError;
+use crate::fragment::{Fragment, Pasted};
+use crate::layout::{IdMapping, Layout};
+use crate::models::{BlockId, BlockKind, ConnectionId, ConnectionType, Endpoint, GroupId, Transform, TurnoutId};
+
+// Every edit of a layout is described by a `Command` and run through `History::execute`,
+// which keeps what is needed to undo and redo it. Steps store the groups, connections and
+// turnouts the command touched as they were before it, so undoing never depends on a command
+// being reversible, and a step only costs as much as its command changed.
+
+// Oldest steps are forgotten beyond this
+pub const HISTORY_LIMIT: usize = 100;
+
+// MARK: - Command
+#[derive(Debug, Clone)]
+pub enum Command {
+    // `None` places a straight oriented along its neighbors
+    PlaceBlock { grid_pos: (i32, i32), kind: Option<BlockKind> },
+    // Straight run from one cell towards another, see `Layout::straight_run`
+    LayRun { start: (i32, i32), end: (i32, i32) },
+    RemoveBlocks(Vec<BlockId>),
+    MoveBlocks { blocks: Vec<BlockId>, offset: (i32, i32) },
+    TransformBlocks { blocks: Vec<BlockId>, transform: Transform },
+    // Paste a fragment with its top-left corner at the given cell
+    Paste { fragment: Fragment, origin: (i32, i32) },
+    MergeGroups(GroupId, GroupId),
+    SplitGroupAt(BlockId),
+    // Make an end block the start or the end of its group
+    SetGroupEnd { block: BlockId, end: ConnectionType },
+    RenameGroup { group: GroupId, name: Option<String> },
+    Connect { from: Endpoint, to: Endpoint, directed: bool },
+    // Several connections in one step, e.g. accepted suggestions
+    ConnectMany { pairs: Vec<(Endpoint, Endpoint)>, directed: bool },
+    RemoveConnection(ConnectionId),
+    ReverseConnection(ConnectionId),
+    // Reattach a connection or change its direction
+    EditConnection { id: ConnectionId, from: Endpoint, to: Endpoint, directed: bool },
+    ClearConnections,
+    AddTurnout { points: BlockId, normal: BlockId, reverse: BlockId },
+    ToggleTurnout(TurnoutId),
+    RemoveTurnout(TurnoutId),
+    CompactIds,
+    // Replace the whole layout, e.g. with one loaded from disk
+    Load(Box<Layout>),
+}
+
+impl Command {
+    // Short description for the history panel
+    pub fn label(&self) -> String {
+        match self {
+            Command::PlaceBlock { grid_pos, kind } => {
+                let piece = kind.map_or("Straight", |kind| kind.label());
+                format!("Place {} at {:?}", piece, grid_pos)
+            }
+            Command::LayRun { start, end } => format!("Lay track from {:?} to {:?}", start, end),
+            Command::RemoveBlocks(blocks) if blocks.len() == 1 => "Remove block".to_string(),
+            Command::RemoveBlocks(blocks) => format!("Remove {} blocks", blocks.len()),
+            Command::MoveBlocks { blocks, offset } if blocks.len() == 1 => format!("Move block by {:?}", offset),
+            Command::MoveBlocks { blocks, offset } => format!("Move {} blocks by {:?}", blocks.len(), offset),
+            Command::TransformBlocks { blocks, transform } => format!("{} {} blocks", transform.label(), blocks.len()),
+            Command::Paste { fragment, .. } => {
+                let blocks: usize = fragment.groups.iter().map(|group| group.blocks.len()).sum();
+                format!("Paste {} blocks", blocks)
+            }
+            Command::MergeGroups(a, b) => format!("Merge groups {} and {}", a, b),
+            Command::SplitGroupAt(block) => format!("Split group at block {}", block),
+            Command::SetGroupEnd { block, end } => format!("Set block {} as {:?}", block, end),
+            Command::RenameGroup { group, .. } => format!("Rename group {}", group),
+            Command::Connect { from, to, .. } => format!("Connect {} to {}", from, to),
+            Command::ConnectMany { pairs, .. } if pairs.len() == 1 => "Add 1 connection".to_string(),
+            Command::ConnectMany { pairs, .. } => format!("Add {} connections", pairs.len()),
+            Command::RemoveConnection(id) => format!("Remove connection {}", id),
+            Command::ReverseConnection(id) => format!("Reverse connection {}", id),
+            Command::EditConnection { id, .. } => format!("Edit connection {}", id),
+            Command::ClearConnections => "Remove all connections".to_string(),
+            Command::AddTurnout { .. } => "Add turnout".to_string(),
+            Command::ToggleTurnout(id) => format!("Toggle turnout {}", id),
+            Command::RemoveTurnout(id) => format!("Remove turnout {}", id),
+            Command::CompactIds => "Compact ids".to_string(),
+            Command::Load(_) => "Load layout".to_string(),
+        }
+    }
+
+    fn apply(self, layout: &mut Layout) -> Result<Outcome, LayoutError> {
+        let outcome = match self {
+            Command::PlaceBlock { grid_pos, kind: Some(kind) } => Outcome::Block(layout.place_block(grid_pos, kind)?),
+            Command::PlaceBlock { grid_pos, kind: None } => Outcome::Block(layout.place_block_auto(grid_pos)?),
+            Command::LayRun { start, end } => Outcome::Blocks(layout.lay_run(start, end)?),
+            Command::RemoveBlocks(blocks) => {
+                layout.remove_blocks(&blocks)?;
+                Outcome::None
+            }
+            Command::MoveBlocks { blocks, offset } => {
+                layout.move_blocks(&blocks, offset)?;
+                Outcome::None
+            }
+            Command::TransformBlocks { blocks, transform } => {
+                layout.transform_blocks(&blocks, transform)?;
+                Outcome::None
+            }
+            Command::Paste { fragment, origin } => Outcome::Pasted(layout.paste_fragment(&fragment, origin)?),
+            Command::MergeGroups(a, b) => {
+                layout.merge_groups(a, b)?;
+                Outcome::None
+            }
+            Command::SplitGroupAt(block) => Outcome::Group(layout.split_group_at(block)?),
+            Command::SetGroupEnd { block, end } => {
+                layout.set_group_end(block, end)?;
+                Outcome::None
+            }
+            Command::RenameGroup { group, name } => {
+                layout.rename_group(group, name)?;
+                Outcome::None
+            }
+            Command::Connect { from, to, directed } => {
+                Outcome::Connection(layout.connect_endpoints(from, to, directed)?)
+            }
+            Command::RemoveConnection(id) => {
+                layout.remove_connection(id)?;
+                Outcome::None
+            }
+            Command::ConnectMany { pairs, directed } => {
+                for (from, to) in pairs {
+                    layout.connect_endpoints(from, to, directed)?;
+                }
+                Outcome::None
+            }
+            Command::ReverseConnection(id) => {
+                layout.reverse_connection(id)?;
+                Outcome::None
+            }
+            Command::EditConnection { id, from, to, directed } => {
+                layout.update_connection(id, from, to, directed)?;
+                Outcome::None
+            }
+            Command::ClearConnections => {
+                layout.clear_connections();
+                Outcome::None
+            }
+            Command::AddTurnout { points, normal, reverse } => {
+                Outcome::Turnout(layout.add_turnout(points, normal, reverse)?)
+            }
+            Command::ToggleTurnout(id) => {
+                layout.toggle_turnout(id)?;
+                Outcome::None
+            }
+            Command::RemoveTurnout(id) => {
+                layout.remove_turnout(id)?;
+                Outcome::None
+            }
+            Command::CompactIds => Outcome::Compacted(layout.compact_ids()),
+            Command::Load(loaded) => {
+                layout.replace(*loaded);
+                Outcome::None
+            }
+        };
+        Ok(outcome)
+    }
+}
+
+// MARK: - Outcome
+// What a command created, for callers that want to follow up on it
+#[derive(Debug, Clone)]
+pub enum Outcome {
+    None,
+    Block(BlockId),
+    Blocks(Vec<BlockId>),
+    Group(GroupId),
+    Connection(ConnectionId),
+    Turnout(TurnoutId),
+    Compacted(IdMapping),
+    Pasted(Pasted),
+}
+
+// MARK: - History
+#[derive(Debug, Clone)]
+struct Step {
+    label: String,
+    // Takes the layout to the other side of this step
+    change: Change,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    undo: Vec<Step>,
+    redo: Vec<Step>,
+    // Moves on whenever the layout changes through the history
+    revision: u64,
+    // Groups changed by each of the latest revisions, oldest first
+    changed: VecDeque<(u64, Vec<GroupId>)>,
+}
+
+impl History {
+    // Run a command and record it. A failing command leaves the layout untouched
+    // and is not recorded.
+    pub fn execute(&mut self, layout: &mut Layout, command: Command) -> Result<Outcome, LayoutError> {
+        let label = command.label();
+        layout.begin_change();
+        let result = command.apply(layout);
+        let change = layout.end_change();
+        let outcome = match result {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                layout.apply_change(change);
+                return Err(e);
+            }
+        };
+
+        self.advance(&change);
+        self.undo.push(Step { label, change });
+        if self.undo.len() > HISTORY_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+        Ok(outcome)
+    }
+
+    // Returns the label of the undone step
+    pub fn undo(&mut self, layout: &mut Layout) -> Option<String> {
+        let step = self.undo.pop()?;
+        let change = layout.apply_change(step.change);
+        self.advance(&change);
+        self.redo.push(Step { label: step.label.clone(), change });
+        Some(step.label)
+    }
+
+    // Returns the label of the redone step
+    pub fn redo(&mut self, layout: &mut Layout) -> Option<String> {
+        let step = self.redo.pop()?;
+        let change = layout.apply_change(step.change);
+        self.advance(&change);
+        self.undo.push(Step { label: step.label.clone(), change });
+        Some(step.label)
+    }
+
+    fn advance(&mut self, change: &Change) {
+        self.revision += 1;
+        self.changed.push_back((self.revision, change.groups().collect()));
+        if self.changed.len() > HISTORY_LIMIT {
+            self.changed.pop_front();
+        }
+    }
+
+    // Lets views that cache what they draw notice that the layout has changed
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    // Groups added, changed or removed since the given revision, so views can update just
+    // those. `None` when that revision is too long ago to tell.
+    pub fn changed_groups_since(&self, revision: u64) -> Option<HashSet<GroupId>> {
+        if revision > self.revision {
+            return None;
+        }
+        let oldest = self.changed.front().map_or(self.revision + 1, |(oldest, _)| *oldest);
+        if revision + 1 < oldest && revision < self.revision {
+            return None;
+        }
+        Some(self.changed.iter()
+            .filter(|(changed, _)| *changed > revision)
+            .flat_map(|(_, groups)| groups.iter().copied())
+            .collect())
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    // Labels of the steps that can be undone, oldest first
+    pub fn undo_labels(&self) -> impl Iterator<Item = &str> {
+        self.undo.iter().map(|step| step.label.as_str())
+    }
+
+    // Labels of the steps that can be redone, next one first
+    pub fn redo_labels(&self) -> impl Iterator<Item = &str> {
+        self.redo.iter().rev().map(|step| step.label.as_str())
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Direction, Group};
+    use std::collections::HashMap;
+
+    const HORIZONTAL: BlockKind = BlockKind::Straight(Direction::Horizontal);
+
+    // Every block can be found through the index where its group says it is
+    fn assert_indexed(layout: &Layout) {
+        let mut count = 0;
+        for group in layout.groups.values() {
+            for block in &group.blocks {
+                let found = layout.block_at(block.grid_pos).map(|(owner, found)| (owner.id, found.id));
+                assert_eq!(found, Some((group.id, block.id)));
+                count += 1;
+            }
+        }
+        assert_eq!(layout.block_count(), count);
+    }
+
+    fn place(history: &mut History, layout: &mut Layout, grid_pos: (i32, i32)) {
+        history.execute(layout, Command::PlaceBlock { grid_pos, kind: Some(HORIZONTAL) }).unwrap();
+    }
+
+    #[test]
+    fn undo_and_redo_restore_groups_and_index() {
+        let mut layout = Layout::default();
+        let mut history = History::default();
+        for x in [0, 1, 2, 3, 5, 6, 7, 8] {
+            place(&mut history, &mut layout, (x, 0));
+        }
+        let before: HashMap<GroupId, Group> = layout.groups.clone();
+        let counter = layout.next_block_id;
+
+        // Joins both runs into one group
+        place(&mut history, &mut layout, (4, 0));
+        assert_eq!(layout.groups.len(), 1);
+        let after = layout.groups.clone();
+        assert_indexed(&layout);
+
+        assert_eq!(history.undo(&mut layout).as_deref(), Some("Place Straight at (4, 0)"));
+        assert_eq!(layout.groups, before);
+        assert_eq!(layout.next_block_id, counter);
+        assert!(!layout.is_occupied((4, 0)));
+        assert_indexed(&layout);
+
+        history.redo(&mut layout);
+        assert_eq!(layout.groups, after);
+        assert_indexed(&layout);
+    }
+
+    #[test]
+    fn failing_command_is_not_recorded() {
+        let mut layout = Layout::default();
+        let mut history = History::default();
+        for row in 0..3 {
+            place(&mut history, &mut layout, (0, row * 2));
+        }
+        let ends: Vec<Endpoint> = [0, 2, 4].iter()
+            .map(|row| layout.endpoint_of_block(layout.block_at((0, *row)).unwrap().1.id).unwrap())
+            .collect();
+        history.execute(&mut layout, Command::Connect { from: ends[0], to: ends[1], directed: false }).unwrap();
+        let labels: Vec<String> = history.undo_labels().map(str::to_string).collect();
+
+        let command = Command::Connect { from: ends[0], to: ends[2], directed: false };
+        assert!(matches!(history.execute(&mut layout, command), Err(LayoutError::EndpointInUse(_))));
+        assert_eq!(layout.connections.len(), 1);
+        assert_eq!(layout.next_connection_id, 2);
+        assert!(history.undo_labels().eq(labels.iter().map(String::as_str)));
+    }
+
+    #[test]
+    fn command_failing_halfway_is_rolled_back() {
+        let mut layout = Layout::default();
+        let mut history = History::default();
+        for row in 0..3 {
+            place(&mut history, &mut layout, (0, row * 2));
+        }
+        let ends: Vec<Endpoint> = [0, 2, 4].iter()
+            .map(|row| layout.endpoint_of_block(layout.block_at((0, *row)).unwrap().1.id).unwrap())
+            .collect();
+
+        // The second pair reuses an endpoint the first one just took
+        let command = Command::ConnectMany { pairs: vec![(ends[0], ends[1]), (ends[0], ends[2])], directed: false };
+        assert!(history.execute(&mut layout, command).is_err());
+        assert!(layout.connections.is_empty());
+        assert_eq!(layout.next_connection_id, 1);
+        assert_eq!(history.revision(), 3);
+        assert_indexed(&layout);
+    }
+
+    #[test]
+    fn reports_only_the_groups_a_command_touched() {
+        let mut layout = Layout::default();
+        let mut history = History::default();
+        history.execute(&mut layout, Command::LayRun { start: (0, 0), end: (3, 0) }).unwrap();
+        history.execute(&mut layout, Command::LayRun { start: (0, 2), end: (3, 2) }).unwrap();
+        let first = layout.block_at((0, 0)).unwrap().0.id;
+        let revision = history.revision();
+
+        place(&mut history, &mut layout, (4, 0));
+        assert_eq!(history.changed_groups_since(revision), Some(HashSet::from([first])));
+        history.undo(&mut layout);
+        assert_eq!(history.changed_groups_since(revision), Some(HashSet::from([first])));
+        assert_eq!(history.changed_groups_since(history.revision()), Some(HashSet::new()));
+    }
+}
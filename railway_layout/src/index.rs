@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use crate::models::{Block, BlockId, Group, GroupId};
+
+// MARK: - CellIndex
+// Where every block is: the block in each occupied cell, and the group and position within
+// `Group::blocks` of every block. `Layout` keeps it up to date through all of its edits, so
+// cell and block lookups do not have to scan the groups.
+// Positions are kept as ordinals counted from the group's first block, so a block can be put
+// in front of a group without renumbering the blocks after it.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CellIndex {
+    cells: HashMap<(i32, i32), BlockId>,
+    ordinals: HashMap<BlockId, (GroupId, i64)>,
+    // Ordinal of the first block of every group
+    firsts: HashMap<GroupId, i64>,
+}
+
+impl CellIndex {
+    pub(crate) fn build<'a>(groups: impl IntoIterator<Item = &'a Group>) -> Self {
+        let mut index = CellIndex::default();
+        for group in groups {
+            index.insert_group(group);
+        }
+        index
+    }
+
+    // Record every block of a group where it is now, e.g. after the group was created,
+    // reordered or took over blocks of another group
+    pub(crate) fn insert_group(&mut self, group: &Group) {
+        self.firsts.insert(group.id, 0);
+        for (slot, block) in group.blocks.iter().enumerate() {
+            self.insert_block(group.id, slot, block);
+        }
+    }
+
+    // Record a single block added after the last block of its group
+    pub(crate) fn insert_block(&mut self, group_id: GroupId, slot: usize, block: &Block) {
+        let first = self.firsts.get(&group_id).copied().unwrap_or(0);
+        self.cells.insert(block.grid_pos, block.id);
+        self.ordinals.insert(block.id, (group_id, first + slot as i64));
+    }
+
+    // Record a single block added before the first block of its group
+    pub(crate) fn insert_first(&mut self, group_id: GroupId, block: &Block) {
+        let first = self.firsts.entry(group_id).or_insert(0);
+        *first -= 1;
+        self.cells.insert(block.grid_pos, block.id);
+        self.ordinals.insert(block.id, (group_id, *first));
+    }
+
+    // Forget a block at the given cell, before it is removed or moved away
+    pub(crate) fn remove_block(&mut self, block_id: BlockId, grid_pos: (i32, i32)) {
+        if self.cells.get(&grid_pos) == Some(&block_id) {
+            self.cells.remove(&grid_pos);
+        }
+        self.ordinals.remove(&block_id);
+    }
+
+    pub(crate) fn block_at(&self, grid_pos: (i32, i32)) -> Option<BlockId> {
+        self.cells.get(&grid_pos).copied()
+    }
+
+    pub(crate) fn slot(&self, block_id: BlockId) -> Option<(GroupId, usize)> {
+        let (group_id, ordinal) = *self.ordinals.get(&block_id)?;
+        let first = self.firsts.get(&group_id).copied().unwrap_or(0);
+        Some((group_id, (ordinal - first) as usize))
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.ordinals.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{BlockKind, Direction};
+
+    fn block(id: u32, x: i32) -> Block {
+        Block { id: BlockId(id), grid_pos: (x, 0), kind: BlockKind::Straight(Direction::Horizontal) }
+    }
+
+    fn group(id: u32, blocks: Vec<Block>) -> Group {
+        Group { id: GroupId(id), blocks, direction: None, start_block_id: None, end_block_id: None, name: None, reversed: false }
+    }
+
+    #[test]
+    fn slots_follow_blocks_added_at_either_end() {
+        let mut index = CellIndex::build([&group(1, vec![block(1, 0), block(2, 1)])]);
+        index.insert_block(GroupId(1), 2, &block(3, 2));
+        index.insert_first(GroupId(1), &block(4, -1));
+        index.insert_first(GroupId(1), &block(5, -2));
+
+        let slots = [5, 4, 1, 2, 3].map(|id| index.slot(BlockId(id)));
+        assert_eq!(slots, [0, 1, 2, 3, 4].map(|slot: usize| Some((GroupId(1), slot))));
+        assert_eq!(index.block_at((-2, 0)), Some(BlockId(5)));
+        assert_eq!(index.len(), 5);
+    }
+
+    #[test]
+    fn removing_a_block_frees_its_cell_only_if_it_still_holds_it() {
+        let mut index = CellIndex::build([&group(1, vec![block(1, 0)]), &group(2, vec![block(2, 5)])]);
+        // Block 2 moved onto cell 0 after block 1 was recorded there
+        index.insert_block(GroupId(2), 0, &Block { grid_pos: (0, 0), ..block(2, 0) });
+        index.remove_block(BlockId(1), (0, 0));
+
+        assert_eq!(index.block_at((0, 0)), Some(BlockId(2)));
+        assert_eq!(index.slot(BlockId(1)), None);
+        assert_eq!(index.len(), 1);
+    }
+
+    #[test]
+    fn reinserting_a_group_renumbers_it_from_its_first_block() {
+        let mut index = CellIndex::default();
+        index.insert_group(&group(1, vec![block(1, 0)]));
+        index.insert_first(GroupId(1), &block(2, -1));
+        index.insert_group(&group(1, vec![block(2, -1), block(1, 0)]));
+
+        assert_eq!(index.slot(BlockId(2)), Some((GroupId(1), 0)));
+        assert_eq!(index.slot(BlockId(1)), Some((GroupId(1), 1)));
+    }
+}
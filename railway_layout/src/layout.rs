@@ -0,0 +1,1493 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use crate::change::Change;
+use crate::error::LayoutError;
+use crate::format;
+use crate::index::CellIndex;
+use crate::models::{
+    Block, BlockId, BlockKind, Connection, ConnectionId, ConnectionType, Direction, Endpoint, Group, GroupId,
+    Port, Transform, Turnout, TurnoutId, TurnoutPosition,
+};
+use crate::table::EndpointTable;
+
+// MARK: - Layout
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Layout {
+    pub groups: HashMap<GroupId, Group>,
+    // Every connection and turnout, indexed by the endpoints they are attached to
+    #[serde(default)]
+    pub connections: EndpointTable<Connection>,
+    #[serde(default)]
+    pub turnouts: EndpointTable<Turnout>,
+    // Id counters only ever grow; see `compact_ids` for the explicit renumbering
+    pub next_block_id: u32,
+    pub next_group_id: u32,
+    #[serde(default = "first_id")]
+    pub next_turnout_id: u32,
+    #[serde(default = "first_id")]
+    pub next_connection_id: u32,
+    // Cell and block lookups; rebuilt after loading, see `rebuild_index`
+    #[serde(skip)]
+    pub(crate) index: CellIndex,
+    // What the current edit changed so far, while `History` records one
+    #[serde(skip)]
+    pub(crate) journal: Option<Change>,
+}
+
+// A block with the position and kind it is about to get
+type Relocation = (BlockId, (i32, i32), BlockKind);
+
+fn first_id() -> u32 {
+    1
+}
+
+// MARK: - Layout - Default
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            groups: HashMap::new(),
+            connections: EndpointTable::default(),
+            turnouts: EndpointTable::default(),
+            next_block_id: 1,
+            next_group_id: 1,
+            next_turnout_id: 1,
+            next_connection_id: 1,
+            index: CellIndex::default(),
+            journal: None,
+        }
+    }
+}
+
+// MARK: - Queries
+impl Layout {
+    pub fn block_count(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_occupied(&self, grid_pos: (i32, i32)) -> bool {
+        self.index.block_at(grid_pos).is_some()
+    }
+
+    // Find the group that owns the given block
+    pub fn group_of_block(&self, block_id: BlockId) -> Option<&Group> {
+        let (group_id, _) = self.index.slot(block_id)?;
+        self.groups.get(&group_id)
+    }
+
+    pub fn block(&self, block_id: BlockId) -> Option<&Block> {
+        self.block_in_group(block_id).map(|(_, block)| block)
+    }
+
+    // Find the block occupying the given cell and the group that owns it
+    pub fn block_at(&self, grid_pos: (i32, i32)) -> Option<(&Group, &Block)> {
+        self.block_in_group(self.index.block_at(grid_pos)?)
+    }
+
+    fn block_in_group(&self, block_id: BlockId) -> Option<(&Group, &Block)> {
+        let (group_id, slot) = self.index.slot(block_id)?;
+        let group = self.groups.get(&group_id)?;
+        group.blocks.get(slot).map(|block| (group, block))
+    }
+
+    // Find groups whose open end would join a block of the given kind placed at `pos`
+    pub fn find_neighboring_groups(&self, pos: (i32, i32), kind: BlockKind) -> Vec<GroupId> {
+        let mut neighbors = Vec::new();
+        for (_, group_id) in self.link_candidates(pos, kind) {
+            if !neighbors.contains(&group_id) {
+                neighbors.push(group_id);
+            }
+        }
+        neighbors
+    }
+
+    // Ports of a block of `kind` at `pos` that meet an open end of a neighboring group
+    fn link_candidates(&self, pos: (i32, i32), kind: BlockKind) -> Vec<(Port, GroupId)> {
+        let new_block = Block { id: BlockId(0), grid_pos: pos, kind };
+        kind.ports().into_iter()
+            .filter_map(|port| {
+                let (dx, dy) = port.offset();
+                let (group, block) = self.block_at((pos.0 + dx, pos.1 + dy))?;
+                let linked = block.links_to(&new_block) && group.can_extend(block.id, port.opposite());
+                linked.then_some((port, group.id))
+            })
+            .collect()
+    }
+
+    // Check if a block of the given kind can be added to a group at `pos`,
+    // which requires its track to meet one of the group's open ends
+    pub fn can_add_to_group(&self, group_id: GroupId, pos: (i32, i32), kind: BlockKind) -> Result<(), LayoutError> {
+        if !self.groups.contains_key(&group_id) {
+            return Err(LayoutError::GroupNotFound(group_id));
+        }
+        if self.link_candidates(pos, kind).iter().any(|(_, id)| *id == group_id) {
+            Ok(())
+        } else {
+            Err(LayoutError::NotAligned { group_id, grid_pos: pos })
+        }
+    }
+}
+
+// MARK: - Group methods
+impl Layout {
+    // Place a new block of the given kind, extending the group whose open end it meets.
+    // When the block's track also meets a second group the two are merged through it.
+    // Returns the id of the new block.
+    pub fn place_block(&mut self, grid_pos: (i32, i32), kind: BlockKind) -> Result<BlockId, LayoutError> {
+        if self.is_occupied(grid_pos) {
+            return Err(LayoutError::PositionOccupied(grid_pos));
+        }
+
+        let candidates = self.link_candidates(grid_pos, kind);
+        let block_id = self.alloc_block_id();
+        let new_block = Block { id: block_id, grid_pos, kind };
+
+        let Some(&(first_port, first_group)) = candidates.first() else {
+            // Create new group with the new block
+            let group_id = self.alloc_group_id();
+            let mut group = Group {
+                id: group_id,
+                blocks: vec![new_block],
+                direction: None,
+                start_block_id: None,
+                end_block_id: None,
+                name: None,
+                reversed: false,
+            };
+            group.update_start_end_blocks();
+            self.journal_group(group_id);
+            self.groups.insert(group_id, group);
+            self.reindex(group_id);
+            return Ok(block_id);
+        };
+
+        // A second group can only be joined through a route of the new piece
+        let second = candidates.iter()
+            .find(|(port, _)| *port != first_port && kind.has_route(first_port, *port))
+            .copied()
+            .filter(|(_, group_id)| *group_id != first_group);
+        if let Some((second_port, _)) = second {
+            for port in [first_port, second_port] {
+                let (dx, dy) = port.offset();
+                if let Some(end_block) = self.index.block_at((grid_pos.0 + dx, grid_pos.1 + dy)) {
+                    self.check_joined_end(end_block)?;
+                }
+            }
+        }
+
+        // Closing a loop leaves the group without ends, so it has to be ordered afresh
+        let closes_loop = candidates.iter().filter(|(_, group_id)| *group_id == first_group).count() > 1;
+        let (dx, dy) = first_port.offset();
+        match self.index.block_at((grid_pos.0 + dx, grid_pos.1 + dy)) {
+            Some(end_block) if !closes_loop => self.extend_group(first_group, end_block, new_block),
+            _ => {
+                self.journal_group(first_group);
+                if let Some(group) = self.groups.get_mut(&first_group) {
+                    group.blocks.push(new_block);
+                    group.update_start_end_blocks();
+                }
+                self.update_group_direction(first_group);
+                self.reindex(first_group);
+            }
+        }
+
+        // Merge groups if necessary
+        if let Some((_, second_group)) = second {
+            self.merge_groups(second_group, first_group)?;
+        }
+
+        Ok(block_id)
+    }
+
+    // Add a block next to the given end block of a group. The other blocks keep their order, so
+    // only the new block has to be indexed, unless the start is no longer the end with the
+    // smaller grid position (see `Group::update_start_end_blocks`) and the order flips.
+    fn extend_group(&mut self, group_id: GroupId, end_block: BlockId, block: Block) {
+        self.journal_group(group_id);
+        let Some(group) = self.groups.get_mut(&group_id) else {
+            return;
+        };
+        let kind = block.kind;
+        let at_end = group.end_block_id == Some(end_block);
+        if group.blocks.len() < 2 || !(at_end || group.start_block_id == Some(end_block)) {
+            group.blocks.push(block);
+            group.update_start_end_blocks();
+            group.update_direction();
+            self.reindex(group_id);
+            return;
+        }
+
+        if at_end {
+            group.blocks.push(block);
+        } else {
+            group.blocks.insert(0, block);
+        }
+        let (first, last) = (group.blocks[0].grid_pos, group.blocks[group.blocks.len() - 1].grid_pos);
+        let flipped = (first > last) != group.reversed;
+        if flipped {
+            group.blocks.reverse();
+        }
+        group.start_block_id = Some(group.blocks[0].id);
+        group.end_block_id = Some(group.blocks[group.blocks.len() - 1].id);
+
+        // A straight group stays straight only if the new block continues it
+        match group.direction {
+            Some(direction) if kind != BlockKind::Straight(direction) => group.direction = None,
+            None if group.blocks.len() == 2 => group.update_direction(),
+            _ => {}
+        }
+
+        if flipped {
+            self.reindex(group_id);
+        } else if at_end {
+            let slot = group.blocks.len() - 1;
+            self.index.insert_block(group_id, slot, &group.blocks[slot]);
+        } else {
+            self.index.insert_first(group_id, &group.blocks[0]);
+        }
+    }
+
+    // Place a straight block, oriented to continue a neighboring group when there is one.
+    // A lone straight neighbor has no direction yet and is turned to face the new block.
+    pub fn place_block_auto(&mut self, grid_pos: (i32, i32)) -> Result<BlockId, LayoutError> {
+        if self.is_occupied(grid_pos) {
+            return Err(LayoutError::PositionOccupied(grid_pos));
+        }
+
+        let kind = self.auto_kind(grid_pos);
+        if self.best_linked_straight(grid_pos).is_none() {
+            if let Some((group_id, _)) = self.lone_neighbor(grid_pos) {
+                self.journal_group(group_id);
+                if let Some(group) = self.groups.get_mut(&group_id) {
+                    group.blocks[0].kind = kind;
+                }
+            }
+        }
+        self.place_block(grid_pos, kind)
+    }
+
+    // The piece `place_block_auto` would put at a cell
+    pub fn auto_kind(&self, grid_pos: (i32, i32)) -> BlockKind {
+        self.best_linked_straight(grid_pos)
+            .or_else(|| self.lone_neighbor(grid_pos).map(|(_, port)| BlockKind::Straight(port.direction())))
+            .unwrap_or(BlockKind::Straight(Direction::Horizontal))
+    }
+
+    // Straight with the most links to open ends; ties go to the earlier direction
+    fn best_linked_straight(&self, grid_pos: (i32, i32)) -> Option<BlockKind> {
+        let mut best: Option<(usize, BlockKind)> = None;
+        for direction in [Direction::Horizontal, Direction::Vertical, Direction::DiagonalUp, Direction::DiagonalDown] {
+            let kind = BlockKind::Straight(direction);
+            let links = self.link_candidates(grid_pos, kind).len();
+            if links > 0 && best.is_none_or(|(most, _)| links > most) {
+                best = Some((links, kind));
+            }
+        }
+        best.map(|(_, kind)| kind)
+    }
+
+    // A neighboring single straight, which has no direction of its own yet
+    fn lone_neighbor(&self, grid_pos: (i32, i32)) -> Option<(GroupId, Port)> {
+        Port::ALL.into_iter().find_map(|port| {
+            let (dx, dy) = port.offset();
+            let (group, block) = self.block_at((grid_pos.0 + dx, grid_pos.1 + dy))?;
+            let lone_straight = group.blocks.len() == 1 && matches!(block.kind, BlockKind::Straight(_));
+            lone_straight.then_some((group.id, port))
+        })
+    }
+
+    // Cells of the straight run from `start` towards `end`, snapped to the nearest of the
+    // horizontal, vertical and diagonal axes, with the direction of its track
+    pub fn straight_run(start: (i32, i32), end: (i32, i32)) -> (Direction, Vec<(i32, i32)>) {
+        let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+        let (step, length) = if dx.abs() > 2 * dy.abs() {
+            ((dx.signum(), 0), dx.abs())
+        } else if dy.abs() > 2 * dx.abs() {
+            ((0, dy.signum()), dy.abs())
+        } else {
+            ((dx.signum(), dy.signum()), dx.abs().max(dy.abs()))
+        };
+        let direction = Port::from_offset(step).map_or(Direction::Horizontal, Port::direction);
+        let cells = (0..=length).map(|i| (start.0 + step.0 * i, start.1 + step.1 * i)).collect();
+        (direction, cells)
+    }
+
+    // Lay straight track from `start` towards `end` (see `straight_run`) as one run, extending
+    // or joining groups whose open ends it meets. Nothing is placed if any cell is occupied.
+    // The run is built as a group of its own and merged with its neighbors once.
+    // Returns the ids of the new blocks.
+    pub fn lay_run(&mut self, start: (i32, i32), end: (i32, i32)) -> Result<Vec<BlockId>, LayoutError> {
+        let (direction, cells) = Layout::straight_run(start, end);
+        if let Some(occupied) = cells.iter().find(|cell| self.is_occupied(**cell)) {
+            return Err(LayoutError::PositionOccupied(*occupied));
+        }
+        if cells.len() == 1 {
+            return Ok(vec![self.place_block_auto(start)?]);
+        }
+
+        let kind = BlockKind::Straight(direction);
+        let blocks: Vec<Block> = cells.into_iter()
+            .map(|grid_pos| Block { id: self.alloc_block_id(), grid_pos, kind })
+            .collect();
+        let block_ids = blocks.iter().map(|block| block.id).collect();
+        let group_id = self.alloc_group_id();
+        let mut group = Group {
+            id: group_id,
+            blocks,
+            direction: None,
+            start_block_id: None,
+            end_block_id: None,
+            name: None,
+            reversed: false,
+        };
+        group.update_start_end_blocks();
+        group.update_direction();
+        self.journal_group(group_id);
+        self.groups.insert(group_id, group);
+        self.reindex(group_id);
+        self.merge_with_neighbors(group_id);
+        Ok(block_ids)
+    }
+
+    // Remove the given blocks from all groups.
+    // Returns the number of removed blocks.
+    pub fn remove_blocks(&mut self, block_ids: &[BlockId]) -> Result<usize, LayoutError> {
+        if let Some(missing) = block_ids.iter().find(|id| self.group_of_block(**id).is_none()) {
+            return Err(LayoutError::BlockNotFound(*missing));
+        }
+
+        // Remember the endpoints of every affected group so connections can follow them after a split
+        let removed: HashSet<BlockId> = block_ids.iter().copied().collect();
+        let affected = self.groups_of_blocks(block_ids);
+        let old_endpoints = self.endpoints_of(&affected);
+
+        let before = self.block_count();
+        for group_id in &affected {
+            self.journal_group(*group_id);
+            let Some(group) = self.groups.get_mut(group_id) else {
+                continue;
+            };
+            for block in group.blocks.iter().filter(|block| removed.contains(&block.id)) {
+                self.index.remove_block(block.id, block.grid_pos);
+            }
+            group.blocks.retain(|block| !removed.contains(&block.id));
+            group.update_start_end_blocks(); // Update start and end blocks
+            // Remove empty groups
+            if group.blocks.is_empty() {
+                self.groups.remove(group_id);
+            }
+        }
+
+        // Split groups that are no longer contiguous
+        for group_id in &affected {
+            for id in self.split_group(*group_id) {
+                self.reindex(id);
+            }
+        }
+        self.reassign_endpoints(&old_endpoints);
+
+        Ok(before - self.block_count())
+    }
+
+    // Check that the given blocks can be shifted by `offset` without landing on a block
+    // that stays where it is
+    pub fn can_move_blocks(&self, block_ids: &[BlockId], offset: (i32, i32)) -> Result<(), LayoutError> {
+        self.relocation_targets(block_ids, |block| {
+            ((block.grid_pos.0 + offset.0, block.grid_pos.1 + offset.1), block.kind)
+        })
+        .map(|_| ())
+    }
+
+    // Shift the given blocks by `offset` grid cells. Groups left in pieces are split, moved
+    // track that now meets an open end is merged into that group, and connections and
+    // turnouts follow the endpoint blocks they were attached to.
+    pub fn move_blocks(&mut self, block_ids: &[BlockId], offset: (i32, i32)) -> Result<(), LayoutError> {
+        let targets = self.relocation_targets(block_ids, |block| {
+            ((block.grid_pos.0 + offset.0, block.grid_pos.1 + offset.1), block.kind)
+        })?;
+        if offset != (0, 0) {
+            self.relocate_blocks(targets);
+        }
+        Ok(())
+    }
+
+    // Rotate or mirror the given blocks within their bounding box, turning each piece of
+    // track with them. Groups are split and merged as for `move_blocks`; a group's start and
+    // end may swap, and connections and turnouts switch ends along with their blocks.
+    pub fn transform_blocks(&mut self, block_ids: &[BlockId], transform: Transform) -> Result<(), LayoutError> {
+        let positions: Vec<(i32, i32)> = block_ids.iter()
+            .filter_map(|id| self.block(*id))
+            .map(|block| block.grid_pos)
+            .collect();
+        let (Some(min_x), Some(max_x)) = (positions.iter().map(|p| p.0).min(), positions.iter().map(|p| p.0).max()) else {
+            return match block_ids.first() {
+                Some(missing) => Err(LayoutError::BlockNotFound(*missing)),
+                None => Ok(()),
+            };
+        };
+        let min_y = positions.iter().map(|p| p.1).min().unwrap_or(0);
+        let max_y = positions.iter().map(|p| p.1).max().unwrap_or(0);
+        let (width, height) = (max_x - min_x, max_y - min_y);
+
+        let targets = self.relocation_targets(block_ids, |block| {
+            let (x, y) = (block.grid_pos.0 - min_x, block.grid_pos.1 - min_y);
+            let (x, y) = match transform {
+                Transform::RotateCw => (height - y, x),
+                Transform::RotateCcw => (y, width - x),
+                Transform::MirrorHorizontal => (width - x, y),
+                Transform::MirrorVertical => (x, height - y),
+            };
+            ((min_x + x, min_y + y), block.kind.transformed(transform))
+        })?;
+        self.relocate_blocks(targets);
+        Ok(())
+    }
+
+    // New position and kind of every given block, or an error if a block is missing
+    // or would land on a block that is not being relocated
+    fn relocation_targets(
+        &self,
+        block_ids: &[BlockId],
+        target: impl Fn(&Block) -> ((i32, i32), BlockKind),
+    ) -> Result<Vec<Relocation>, LayoutError> {
+        let mut targets = Vec::with_capacity(block_ids.len());
+        for block_id in block_ids {
+            let block = self.block(*block_id).ok_or(LayoutError::BlockNotFound(*block_id))?;
+            let (grid_pos, kind) = target(block);
+            targets.push((*block_id, grid_pos, kind));
+        }
+        let relocated: HashSet<BlockId> = block_ids.iter().copied().collect();
+        for (_, grid_pos, _) in &targets {
+            if self.index.block_at(*grid_pos).is_some_and(|id| !relocated.contains(&id)) {
+                return Err(LayoutError::PositionOccupied(*grid_pos));
+            }
+        }
+        Ok(targets)
+    }
+
+    // Put blocks at new positions and kinds, then split, merge and re-attach as needed
+    fn relocate_blocks(&mut self, targets: Vec<Relocation>) {
+        if targets.is_empty() {
+            return;
+        }
+        let new_blocks: HashMap<BlockId, ((i32, i32), BlockKind)> = targets.into_iter()
+            .map(|(id, grid_pos, kind)| (id, (grid_pos, kind)))
+            .collect();
+
+        let block_ids: Vec<BlockId> = new_blocks.keys().copied().collect();
+        let affected = self.groups_of_blocks(&block_ids);
+        let old_endpoints = self.endpoints_of(&affected);
+
+        // Take every moved block out of the index first, as blocks may swap cells
+        for group_id in &affected {
+            if let Some(group) = self.groups.get(group_id) {
+                for block in group.blocks.iter().filter(|block| new_blocks.contains_key(&block.id)) {
+                    self.index.remove_block(block.id, block.grid_pos);
+                }
+            }
+        }
+        for group_id in &affected {
+            self.journal_group(*group_id);
+            if let Some(group) = self.groups.get_mut(group_id) {
+                for block in &mut group.blocks {
+                    if let Some((grid_pos, kind)) = new_blocks.get(&block.id) {
+                        block.grid_pos = *grid_pos;
+                        block.kind = *kind;
+                    }
+                }
+            }
+        }
+
+        let mut moved_groups = vec![];
+        for group_id in &affected {
+            for id in self.split_group(*group_id) {
+                self.journal_group(id);
+                if let Some(group) = self.groups.get_mut(&id) {
+                    group.update_start_end_blocks();
+                }
+                self.update_group_direction(id);
+                self.reindex(id);
+                moved_groups.push(id);
+            }
+        }
+        self.reassign_endpoints(&old_endpoints);
+
+        // Join moved track with whatever open ends it now meets
+        for group_id in moved_groups {
+            self.merge_with_neighbors(group_id);
+        }
+    }
+
+    // Repeatedly merge a group with neighboring groups that meet it end to end.
+    // Returns the id of the merged group.
+    pub(crate) fn merge_with_neighbors(&mut self, mut group_id: GroupId) -> GroupId {
+        loop {
+            let Some(group) = self.groups.get(&group_id) else {
+                return group_id;
+            };
+            let neighbors: Vec<GroupId> = group.blocks.iter()
+                .filter(|block| group.is_endpoint(block.id))
+                .flat_map(|block| block.kind.ports().into_iter().map(move |port| {
+                    let (dx, dy) = port.offset();
+                    (block.grid_pos.0 + dx, block.grid_pos.1 + dy)
+                }))
+                .filter_map(|pos| self.block_at(pos).map(|(neighbor, _)| neighbor.id))
+                .filter(|id| *id != group_id)
+                .collect();
+
+            // The surviving group keeps the lower id
+            let Some((keep, _)) = neighbors.into_iter()
+                .map(|neighbor| (neighbor.min(group_id), neighbor.max(group_id)))
+                .find(|(keep, gone)| self.merge_groups(*gone, *keep).is_ok())
+            else {
+                return group_id;
+            };
+            group_id = keep;
+        }
+    }
+
+    // Split a group into its runs of linked track, bringing the direction of each up to date.
+    // The first run keeps the group id, every other run gets a fresh one.
+    // Returns the ids of all resulting groups.
+    pub fn split_group(&mut self, group_id: GroupId) -> Vec<GroupId> {
+        self.journal_group(group_id);
+        let Some(group) = self.groups.get_mut(&group_id) else {
+            return vec![];
+        };
+
+        let mut components = connected_components(&group.blocks);
+        if components.len() <= 1 {
+            // Still one run, but it may have lost the blocks that made it straight or not
+            group.update_direction();
+            return vec![group_id];
+        }
+
+        group.blocks = components.remove(0);
+        let mut result = vec![group_id];
+        for blocks in components {
+            let new_id = self.alloc_group_id();
+            self.journal_group(new_id);
+            self.groups.insert(new_id, Group {
+                id: new_id,
+                blocks,
+                direction: None,
+                start_block_id: None,
+                end_block_id: None,
+                name: None,
+                reversed: false,
+            });
+            result.push(new_id);
+        }
+
+        for id in &result {
+            self.update_group_direction(*id);
+            self.journal_group(*id);
+            if let Some(group) = self.groups.get_mut(id) {
+                group.update_start_end_blocks();
+            }
+            self.reindex(*id);
+        }
+        result
+    }
+
+    // Cut a group between the given block and the next one towards its end; on the end block
+    // itself, between it and the one before. The part holding the start keeps the group id
+    // and name. Returns the id of the other part.
+    pub fn split_group_at(&mut self, block_id: BlockId) -> Result<GroupId, LayoutError> {
+        let group = self.group_of_block(block_id).ok_or(LayoutError::BlockNotFound(block_id))?;
+        if group.blocks.len() < 2 {
+            return Err(LayoutError::CannotSplit(block_id));
+        }
+        let group_id = group.id;
+        let index = group.blocks.iter().position(|block| block.id == block_id).unwrap_or(0);
+        let cut = (index + 1).min(group.blocks.len() - 1);
+
+        let old_endpoints = self.endpoints_of(&[group_id]);
+        let new_id = self.alloc_group_id();
+        self.journal_group(group_id);
+        let Some(group) = self.groups.get_mut(&group_id) else {
+            return Err(LayoutError::GroupNotFound(group_id));
+        };
+        let blocks = group.blocks.split_off(cut);
+        let reversed = group.reversed;
+        self.journal_group(new_id);
+        self.groups.insert(new_id, Group {
+            id: new_id,
+            blocks,
+            direction: None,
+            start_block_id: None,
+            end_block_id: None,
+            name: None,
+            reversed,
+        });
+        for id in [group_id, new_id] {
+            self.update_group_direction(id);
+            self.journal_group(id);
+            if let Some(group) = self.groups.get_mut(&id) {
+                group.update_start_end_blocks();
+            }
+            self.reindex(id);
+        }
+        self.reassign_endpoints(&old_endpoints);
+        Ok(new_id)
+    }
+
+    // Make the given end block the start or the end of its group, swapping the two ends.
+    // Connections and turnouts stay on the same blocks.
+    pub fn set_group_end(&mut self, block_id: BlockId, end: ConnectionType) -> Result<(), LayoutError> {
+        let current = self.endpoint_of_block(block_id)?;
+        let group_id = current.group;
+        let group = &self.groups[&group_id];
+        if current.end == end || group.start_block_id == group.end_block_id {
+            return Ok(());
+        }
+
+        let old_endpoints = self.endpoints_of(&[group_id]);
+        self.journal_group(group_id);
+        if let Some(group) = self.groups.get_mut(&group_id) {
+            group.reversed = !group.reversed;
+            group.update_start_end_blocks();
+        }
+        self.reindex(group_id);
+        self.reassign_endpoints(&old_endpoints);
+        Ok(())
+    }
+
+    pub fn rename_group(&mut self, group_id: GroupId, name: Option<String>) -> Result<(), LayoutError> {
+        self.journal_group(group_id);
+        let group = self.groups.get_mut(&group_id).ok_or(LayoutError::GroupNotFound(group_id))?;
+        group.name = name.filter(|name| !name.trim().is_empty());
+        Ok(())
+    }
+
+    // Groups owning any of the given blocks, in the order first met
+    fn groups_of_blocks(&self, block_ids: &[BlockId]) -> Vec<GroupId> {
+        let mut groups = vec![];
+        let mut seen = HashSet::new();
+        for group_id in block_ids.iter().filter_map(|id| self.index.slot(*id)).map(|(group_id, _)| group_id) {
+            if seen.insert(group_id) {
+                groups.push(group_id);
+            }
+        }
+        groups
+    }
+
+    fn endpoints_of(&self, group_ids: &[GroupId]) -> HashMap<GroupId, (Option<BlockId>, Option<BlockId>)> {
+        group_ids.iter()
+            .filter_map(|id| self.groups.get(id))
+            .map(|group| (group.id, (group.start_block_id, group.end_block_id)))
+            .collect()
+    }
+
+    // Re-attach connections and turnouts of the given (pre-edit) group endpoints to whichever group
+    // now owns the matching start or end block. Those whose endpoint block is gone are dropped.
+    fn reassign_endpoints(&mut self, old_endpoints: &HashMap<GroupId, (Option<BlockId>, Option<BlockId>)>) {
+        let mut connections = std::mem::take(&mut self.connections);
+        connections.remap(|endpoint| self.remap_endpoint(old_endpoints, endpoint));
+        self.connections = connections;
+
+        let mut turnouts = std::mem::take(&mut self.turnouts);
+        turnouts.remap(|endpoint| self.remap_endpoint(old_endpoints, endpoint));
+        self.turnouts = turnouts;
+    }
+
+    fn remap_endpoint(
+        &self,
+        old_endpoints: &HashMap<GroupId, (Option<BlockId>, Option<BlockId>)>,
+        endpoint: Endpoint,
+    ) -> Option<Endpoint> {
+        let Some((old_start, old_end)) = old_endpoints.get(&endpoint.group) else {
+            // Untouched group
+            return self.groups.contains_key(&endpoint.group).then_some(endpoint);
+        };
+
+        let block_id = match endpoint.end {
+            ConnectionType::Start => (*old_start)?,
+            ConnectionType::End => (*old_end)?,
+        };
+        let group = self.group_of_block(block_id)?;
+
+        // Prefer the same end when a single-block group is both start and end
+        let end = if endpoint.end == ConnectionType::End && group.end_block_id == Some(block_id) {
+            ConnectionType::End
+        } else if group.start_block_id == Some(block_id) {
+            ConnectionType::Start
+        } else if group.end_block_id == Some(block_id) {
+            ConnectionType::End
+        } else {
+            return None;
+        };
+        Some(Endpoint { group: group.id, end })
+    }
+
+    // Update the direction of a group based on its blocks
+    pub fn update_group_direction(&mut self, group_id: GroupId) {
+        self.journal_group(group_id);
+        if let Some(group) = self.groups.get_mut(&group_id) {
+            group.update_direction();
+        }
+    }
+
+    // Merge two groups into one
+    pub fn merge_groups(&mut self, group1_id: GroupId, group2_id: GroupId) -> Result<(), LayoutError> {
+        if group1_id == group2_id {
+            return Err(LayoutError::SameGroup(group1_id));
+        }
+        let group1 = self.groups.get(&group1_id).ok_or(LayoutError::GroupNotFound(group1_id))?;
+        let group2 = self.groups.get(&group2_id).ok_or(LayoutError::GroupNotFound(group2_id))?;
+
+        // The groups must meet end to end
+        let ends = |group: &Group| -> Vec<Block> {
+            group.blocks.iter().filter(|block| group.is_endpoint(block.id)).cloned().collect()
+        };
+        let (ends1, ends2) = (ends(group1), ends(group2));
+        let joined = ends1.iter().find_map(|a| ends2.iter().find(|b|
+            a.links_to(b)
+                && a.port_towards(b.grid_pos).is_some_and(|port| group1.can_extend(a.id, port))
+                && b.port_towards(a.grid_pos).is_some_and(|port| group2.can_extend(b.id, port))
+        ).map(|b| (a.id, b.id)));
+        let Some((end1, end2)) = joined else {
+            return Err(LayoutError::NotLinked(group1_id, group2_id));
+        };
+        self.check_joined_end(end1)?;
+        self.check_joined_end(end2)?;
+
+        let old_endpoints = self.endpoints_of(&[group1_id, group2_id]);
+        self.journal_group(group1_id);
+        self.journal_group(group2_id);
+        let group1 = self.groups.remove(&group1_id).ok_or(LayoutError::GroupNotFound(group1_id))?;
+        if let Some(group2) = self.groups.get_mut(&group2_id) {
+            group2.blocks.extend(group1.blocks);
+            group2.update_start_end_blocks(); // Update start and end blocks
+        }
+        self.update_group_direction(group2_id);
+        self.reindex(group2_id);
+
+        // Connections follow their end block; those on the joined ends are dropped
+        self.reassign_endpoints(&old_endpoints);
+        Ok(())
+    }
+
+    // The ends where two groups are joined end up inside the merged group, which would drop a
+    // turnout attached there. Such joins are refused until the turnout is removed.
+    fn check_joined_end(&self, end_block: BlockId) -> Result<(), LayoutError> {
+        let endpoint = self.endpoint_of_block(end_block)?;
+        let group = &self.groups[&endpoint.group];
+        // A lone block stays an end of the merged group
+        if group.start_block_id != group.end_block_id && self.turnouts.at(endpoint).is_some() {
+            return Err(LayoutError::EndpointInUse(endpoint));
+        }
+        Ok(())
+    }
+
+    // Connect the groups owning the two given endpoint blocks
+    pub fn connect_groups(&mut self, from_block: BlockId, to_block: BlockId, directed: bool) -> Result<ConnectionId, LayoutError> {
+        let from = self.endpoint_of_block(from_block)?;
+        let to = self.endpoint_of_block(to_block)?;
+        self.connect_endpoints(from, to, directed)
+    }
+
+    // Connect two endpoints. Each endpoint takes at most one connection or turnout, so
+    // connecting an endpoint that already has either fails with `EndpointInUse`.
+    pub fn connect_endpoints(&mut self, from: Endpoint, to: Endpoint, directed: bool) -> Result<ConnectionId, LayoutError> {
+        self.can_connect(from, to)?;
+
+        let id = ConnectionId(self.next_connection_id);
+        self.connections.insert(Connection { id, from, to, directed })?;
+        self.next_connection_id += 1;
+        Ok(id)
+    }
+
+    // Why `connect_endpoints` would refuse to connect two endpoints, if it would
+    pub fn can_connect(&self, from: Endpoint, to: Endpoint) -> Result<(), LayoutError> {
+        for endpoint in [from, to] {
+            if !self.groups.contains_key(&endpoint.group) {
+                return Err(LayoutError::GroupNotFound(endpoint.group));
+            }
+        }
+        if from == to {
+            return Err(LayoutError::DuplicateEndpoint);
+        }
+        if self.connections.at(from).is_some_and(|conn| conn.other(from) == Some(to)) {
+            return Err(LayoutError::ConnectionExists);
+        }
+        if let Some(endpoint) = [from, to].into_iter().find(|endpoint| self.attached_to(*endpoint).is_some()) {
+            return Err(LayoutError::EndpointInUse(endpoint));
+        }
+        Ok(())
+    }
+
+    // Reattach a connection to other endpoints or change its direction, keeping its id.
+    // The connection is left as it was if the new endpoints cannot take it.
+    pub fn update_connection(&mut self, connection_id: ConnectionId, from: Endpoint, to: Endpoint, directed: bool) -> Result<(), LayoutError> {
+        let old = self.connections.remove(connection_id).ok_or(LayoutError::ConnectionNotFound(connection_id))?;
+        if let Err(e) = self.can_connect(from, to) {
+            self.connections.insert(old)?;
+            return Err(e);
+        }
+        self.connections.insert(Connection { id: connection_id, from, to, directed })?;
+        Ok(())
+    }
+
+    // Swap the ends of a connection, so a directed one runs the other way
+    pub fn reverse_connection(&mut self, connection_id: ConnectionId) -> Result<(), LayoutError> {
+        let conn = self.connections.get(connection_id).ok_or(LayoutError::ConnectionNotFound(connection_id))?.clone();
+        self.update_connection(connection_id, conn.to, conn.from, conn.directed)
+    }
+
+    pub fn remove_connection(&mut self, connection_id: ConnectionId) -> Result<Connection, LayoutError> {
+        self.connections.remove(connection_id).ok_or(LayoutError::ConnectionNotFound(connection_id))
+    }
+
+    // Remove every connection
+    pub fn clear_connections(&mut self) {
+        self.connections.clear();
+    }
+
+    // The connection or turnout attached to an endpoint, if any
+    pub fn attached_to(&self, endpoint: Endpoint) -> Option<Attachment> {
+        if let Some(conn) = self.connections.at(endpoint) {
+            Some(Attachment::Connection(conn.id))
+        } else {
+            self.turnouts.at(endpoint).map(|turnout| Attachment::Turnout(turnout.id))
+        }
+    }
+
+    // Resolve a block to its group and whether it is that group's start or end
+    pub fn endpoint_of_block(&self, block_id: BlockId) -> Result<Endpoint, LayoutError> {
+        let group = self.group_of_block(block_id).ok_or(LayoutError::BlockNotFound(block_id))?;
+        if group.start_block_id == Some(block_id) {
+            Ok(Endpoint { group: group.id, end: ConnectionType::Start })
+        } else if group.end_block_id == Some(block_id) {
+            Ok(Endpoint { group: group.id, end: ConnectionType::End })
+        } else {
+            Err(LayoutError::NotAnEndpoint(block_id))
+        }
+    }
+
+    // The block an endpoint sits on
+    pub fn endpoint_block(&self, endpoint: Endpoint) -> Option<&Block> {
+        let group = self.groups.get(&endpoint.group)?;
+        let block_id = match endpoint.end {
+            ConnectionType::Start => group.start_block_id?,
+            ConnectionType::End => group.end_block_id?,
+        };
+        self.block_in_group(block_id)
+            .filter(|(owner, _)| owner.id == group.id)
+            .map(|(_, block)| block)
+    }
+}
+
+// MARK: - Suggested connections
+impl Layout {
+    // Free group ends whose blocks touch, orthogonally or diagonally, an end of another group.
+    // These are typically corners where the track could not be merged. Each endpoint is
+    // offered at most once, pairing it with the first candidate in group id order.
+    pub fn suggest_connections(&self) -> Vec<(Endpoint, Endpoint)> {
+        let mut group_ids: Vec<GroupId> = self.groups.keys().copied().collect();
+        group_ids.sort();
+
+        let mut free_ends = vec![];
+        for group_id in group_ids {
+            let group = &self.groups[&group_id];
+            for end in [ConnectionType::Start, ConnectionType::End] {
+                // A single block is both ends; offer it once
+                if end == ConnectionType::End && group.start_block_id == group.end_block_id {
+                    continue;
+                }
+                let endpoint = Endpoint { group: group_id, end };
+                if let Some(block) = self.endpoint_block(endpoint).filter(|_| self.attached_to(endpoint).is_none()) {
+                    free_ends.push((endpoint, block.grid_pos));
+                }
+            }
+        }
+
+        let mut suggestions = vec![];
+        let mut used = vec![];
+        for (i, (from, from_pos)) in free_ends.iter().enumerate() {
+            if used.contains(from) {
+                continue;
+            }
+            let partner = free_ends[i + 1..].iter().find(|(to, to_pos)| {
+                to.group != from.group
+                    && !used.contains(to)
+                    && (from_pos.0 - to_pos.0).abs() <= 1
+                    && (from_pos.1 - to_pos.1).abs() <= 1
+            });
+            if let Some((to, _)) = partner {
+                used.extend([*from, *to]);
+                suggestions.push((*from, *to));
+            }
+        }
+        suggestions
+    }
+}
+
+// MARK: - Turnouts
+impl Layout {
+    // Add a turnout joining the points endpoint block to the normal and reverse endpoint blocks.
+    // It starts out in the normal position.
+    pub fn add_turnout(&mut self, points_block: BlockId, normal_block: BlockId, reverse_block: BlockId) -> Result<TurnoutId, LayoutError> {
+        let points = self.endpoint_of_block(points_block)?;
+        let normal = self.endpoint_of_block(normal_block)?;
+        let reverse = self.endpoint_of_block(reverse_block)?;
+
+        // The turnout takes over its endpoints, so none of them may be connected already
+        if let Some(endpoint) = [points, normal, reverse].into_iter().find(|endpoint| self.connections.is_attached(*endpoint)) {
+            return Err(LayoutError::EndpointInUse(endpoint));
+        }
+
+        let id = TurnoutId(self.next_turnout_id);
+        self.turnouts.insert(Turnout {
+            id,
+            points,
+            normal,
+            reverse,
+            position: TurnoutPosition::Normal,
+        })?;
+        self.next_turnout_id += 1;
+        Ok(id)
+    }
+
+    // Throw the turnout to its other position, returning the new position
+    pub fn toggle_turnout(&mut self, turnout_id: TurnoutId) -> Result<TurnoutPosition, LayoutError> {
+        self.turnouts.update(turnout_id, |turnout| {
+            turnout.toggle();
+            turnout.position
+        })
+        .ok_or(LayoutError::TurnoutNotFound(turnout_id))?
+    }
+
+    pub fn remove_turnout(&mut self, turnout_id: TurnoutId) -> Result<Turnout, LayoutError> {
+        self.turnouts.remove(turnout_id).ok_or(LayoutError::TurnoutNotFound(turnout_id))
+    }
+}
+
+// Split blocks into runs whose track is linked together, keeping their original order
+pub(crate) fn connected_components(blocks: &[Block]) -> Vec<Vec<Block>> {
+    let positions: HashMap<(i32, i32), usize> = blocks.iter()
+        .enumerate()
+        .map(|(i, block)| (block.grid_pos, i))
+        .collect();
+    let mut component_of = vec![usize::MAX; blocks.len()];
+    let mut count = 0;
+
+    for start in 0..blocks.len() {
+        if component_of[start] != usize::MAX {
+            continue;
+        }
+        component_of[start] = count;
+        let mut stack = vec![start];
+        while let Some(i) = stack.pop() {
+            let (x, y) = blocks[i].grid_pos;
+            for port in blocks[i].kind.ports() {
+                let (dx, dy) = port.offset();
+                if let Some(&j) = positions.get(&(x + dx, y + dy)) {
+                    if component_of[j] == usize::MAX && blocks[i].links_to(&blocks[j]) {
+                        component_of[j] = count;
+                        stack.push(j);
+                    }
+                }
+            }
+        }
+        count += 1;
+    }
+
+    let mut components = vec![Vec::new(); count];
+    for (block, component) in blocks.iter().zip(component_of) {
+        components[component].push(block.clone());
+    }
+    components
+}
+
+// MARK: - Index
+impl Layout {
+    // Record where the blocks of a group are after it changed
+    pub(crate) fn reindex(&mut self, group_id: GroupId) {
+        if let Some(group) = self.groups.get(&group_id) {
+            self.index.insert_group(group);
+        }
+    }
+
+    // Recompute the cell index from scratch. Only needed after changing `groups` directly
+    // instead of through the methods of `Layout`, which keep it up to date.
+    pub fn rebuild_index(&mut self) {
+        self.index = CellIndex::build(self.groups.values());
+    }
+}
+
+// MARK: - Ids
+impl Layout {
+    pub fn alloc_block_id(&mut self) -> BlockId {
+        let id = BlockId(self.next_block_id);
+        self.next_block_id += 1;
+        id
+    }
+
+    pub fn alloc_group_id(&mut self) -> GroupId {
+        let id = GroupId(self.next_group_id);
+        self.next_group_id += 1;
+        id
+    }
+
+
+    // Make sure the id counters are past every id in use, e.g. after loading a hand-edited file
+    pub fn ensure_id_counters(&mut self) {
+        let max_group = self.groups.keys().map(|id| id.0).max().unwrap_or(0);
+        let max_block = self.groups.values()
+            .flat_map(|group| group.blocks.iter().map(|block| block.id.0))
+            .max()
+            .unwrap_or(0);
+        let max_turnout = self.turnouts.ids().map(|id| id.0).max().unwrap_or(0);
+        let max_connection = self.connections.ids().map(|id| id.0).max().unwrap_or(0);
+        self.next_group_id = self.next_group_id.max(max_group + 1);
+        self.next_block_id = self.next_block_id.max(max_block + 1);
+        self.next_turnout_id = self.next_turnout_id.max(max_turnout + 1);
+        self.next_connection_id = self.next_connection_id.max(max_connection + 1);
+    }
+
+    // Renumber every group, block, connection and turnout densely starting at 1, rewriting all references.
+    // This is the only operation that changes existing ids; callers holding ids should
+    // translate them through the returned mapping.
+    pub fn compact_ids(&mut self) -> IdMapping {
+        let mut mapping = IdMapping::default();
+
+        let mut group_ids: Vec<GroupId> = self.groups.keys().copied().collect();
+        group_ids.sort();
+        for (i, old_id) in group_ids.iter().enumerate() {
+            mapping.groups.insert(*old_id, GroupId(i as u32 + 1));
+        }
+
+        let mut block_ids: Vec<BlockId> = self.groups.values()
+            .flat_map(|group| group.blocks.iter().map(|block| block.id))
+            .collect();
+        block_ids.sort();
+        for (i, old_id) in block_ids.iter().enumerate() {
+            mapping.blocks.insert(*old_id, BlockId(i as u32 + 1));
+        }
+
+        for old_id in &group_ids {
+            self.journal_group(*old_id);
+        }
+        let old_groups = std::mem::take(&mut self.groups);
+        for (old_id, mut group) in old_groups {
+            let new_id = mapping.groups[&old_id];
+            self.journal_group(new_id);
+            group.id = new_id;
+            for block in &mut group.blocks {
+                block.id = mapping.blocks[&block.id];
+            }
+            group.start_block_id = group.start_block_id.map(|id| mapping.blocks[&id]);
+            group.end_block_id = group.end_block_id.map(|id| mapping.blocks[&id]);
+            self.groups.insert(new_id, group);
+        }
+
+        // Records attached to groups that no longer exist are dropped instead of crashing
+        let remap_group = |endpoint: Endpoint| {
+            let group = *mapping.groups.get(&endpoint.group)?;
+            Some(Endpoint { group, ..endpoint })
+        };
+        self.connections.remap(remap_group);
+        self.turnouts.remap(remap_group);
+
+        let mut connections = EndpointTable::default();
+        for (i, mut conn) in self.connections.iter().cloned().enumerate() {
+            let new_id = ConnectionId(i as u32 + 1);
+            mapping.connections.insert(conn.id, new_id);
+            conn.id = new_id;
+            connections.insert(conn).ok();
+        }
+        self.connections.replace(connections);
+
+        let mut turnouts = EndpointTable::default();
+        for (i, mut turnout) in self.turnouts.iter().cloned().enumerate() {
+            let new_id = TurnoutId(i as u32 + 1);
+            mapping.turnouts.insert(turnout.id, new_id);
+            turnout.id = new_id;
+            turnouts.insert(turnout).ok();
+        }
+        self.turnouts.replace(turnouts);
+
+        self.rebuild_index();
+        self.next_group_id = group_ids.len() as u32 + 1;
+        self.next_block_id = block_ids.len() as u32 + 1;
+        self.next_connection_id = self.connections.len() as u32 + 1;
+        self.next_turnout_id = self.turnouts.len() as u32 + 1;
+        mapping
+    }
+}
+
+// MARK: - IdMapping
+// Old id -> new id, as produced by `Layout::compact_ids`
+#[derive(Debug, Clone, Default)]
+pub struct IdMapping {
+    pub groups: HashMap<GroupId, GroupId>,
+    pub blocks: HashMap<BlockId, BlockId>,
+    pub connections: HashMap<ConnectionId, ConnectionId>,
+    pub turnouts: HashMap<TurnoutId, TurnoutId>,
+}
+
+// MARK: - Attachment
+// What is hooked onto a group endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attachment {
+    Connection(ConnectionId),
+    Turnout(TurnoutId),
+}
+
+// MARK: - Serialization
+impl Layout {
+    // Serialize as a document of the current format version
+    pub fn to_json(&self) -> Result<String, LayoutError> {
+        format::to_document(self)
+    }
+
+    // Deserialize a document of any supported format version, migrating it as needed
+    pub fn from_json(contents: &str) -> Result<Layout, LayoutError> {
+        let mut layout = format::from_document(contents)?;
+        layout.ensure_id_counters();
+        layout.rebuild_index();
+        Ok(layout)
+    }
+
+    // Take over everything of another layout, e.g. one loaded from disk, as a single edit
+    pub fn replace(&mut self, other: Layout) {
+        let ids: Vec<GroupId> = self.groups.keys().chain(other.groups.keys()).copied().collect();
+        for group_id in ids {
+            self.journal_group(group_id);
+        }
+        self.groups = other.groups;
+        self.connections.replace(other.connections);
+        self.turnouts.replace(other.turnouts);
+        self.next_block_id = other.next_block_id;
+        self.next_group_id = other.next_group_id;
+        self.next_turnout_id = other.next_turnout_id;
+        self.next_connection_id = other.next_connection_id;
+        self.rebuild_index();
+    }
+
+    // Save the layout to a JSON file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), LayoutError> {
+        std::fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    // Load a layout from a JSON file
+    pub fn load(path: impl AsRef<Path>) -> Result<Layout, LayoutError> {
+        let contents = std::fs::read_to_string(path)?;
+        Layout::from_json(&contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HORIZONTAL: BlockKind = BlockKind::Straight(Direction::Horizontal);
+
+    #[test]
+    fn removing_blocks_updates_direction_of_unsplit_group() {
+        let mut layout = Layout::default();
+        let ids = layout.lay_run((0, 0), (2, 0)).unwrap();
+        let group_id = layout.group_of_block(ids[0]).unwrap().id;
+        assert_eq!(layout.groups[&group_id].direction, Some(Direction::Horizontal));
+
+        layout.remove_blocks(&ids[1..]).unwrap();
+        assert_eq!(layout.groups.len(), 1);
+        assert_eq!(layout.groups[&group_id].direction, None);
+        assert!(layout.validate().is_empty());
+    }
+
+    #[test]
+    fn removing_the_only_curve_makes_a_group_straight() {
+        let mut layout = Layout::default();
+        layout.place_block((0, 0), HORIZONTAL).unwrap();
+        let straight = layout.place_block((1, 0), HORIZONTAL).unwrap();
+        let curve = layout.place_block((2, 0), BlockKind::Curve(Port::West)).unwrap();
+        let group_id = layout.group_of_block(straight).unwrap().id;
+        assert_eq!(layout.groups[&group_id].blocks.len(), 3);
+        assert_eq!(layout.groups[&group_id].direction, None);
+
+        layout.remove_blocks(&[curve]).unwrap();
+        assert_eq!(layout.groups[&group_id].direction, Some(Direction::Horizontal));
+        assert!(layout.validate().is_empty());
+    }
+
+    // Ends, order and index of a group are what ordering it from scratch would give
+    fn assert_ordered(layout: &Layout, group_id: GroupId) {
+        let group = &layout.groups[&group_id];
+        let mut fresh = group.clone();
+        fresh.update_start_end_blocks();
+        fresh.update_direction();
+        assert_eq!(group, &fresh);
+        for block in &group.blocks {
+            assert_eq!(layout.block_at(block.grid_pos).map(|(owner, found)| (owner.id, found.id)), Some((group_id, block.id)));
+        }
+    }
+
+    #[test]
+    fn lay_run_refuses_occupied_cells() {
+        let mut layout = Layout::default();
+        layout.place_block((3, 0), BlockKind::Straight(Direction::Vertical)).unwrap();
+
+        assert!(matches!(layout.lay_run((0, 0), (5, 0)), Err(LayoutError::PositionOccupied((3, 0)))));
+        assert_eq!(layout.block_count(), 1);
+    }
+
+    #[test]
+    fn lay_run_joins_the_open_ends_it_meets() {
+        let mut layout = Layout::default();
+        let left = layout.lay_run((0, 0), (2, 0)).unwrap();
+        let right = layout.lay_run((8, 0), (6, 0)).unwrap();
+        let left_group = layout.group_of_block(left[0]).unwrap().id;
+
+        let middle = layout.lay_run((3, 0), (5, 0)).unwrap();
+        assert_eq!(middle.len(), 3);
+        assert_eq!(layout.groups.len(), 1);
+        let group = &layout.groups[&left_group];
+        assert_eq!(group.blocks.len(), 9);
+        assert_eq!(group.start_block_id, Some(left[0]));
+        assert_eq!(group.end_block_id, Some(right[0]));
+        assert_ordered(&layout, left_group);
+    }
+
+    #[test]
+    fn placing_at_either_end_extends_the_group_in_order() {
+        let mut layout = Layout::default();
+        let run = layout.lay_run((0, 0), (3, 0)).unwrap();
+        let group_id = layout.group_of_block(run[0]).unwrap().id;
+
+        let after = layout.place_block((4, 0), HORIZONTAL).unwrap();
+        let before = layout.place_block((-1, 0), HORIZONTAL).unwrap();
+        let group = &layout.groups[&group_id];
+        assert_eq!(group.start_block_id, Some(before));
+        assert_eq!(group.end_block_id, Some(after));
+        assert_eq!(group.direction, Some(Direction::Horizontal));
+        assert_ordered(&layout, group_id);
+
+        // A curve past the end ends the straight run; the start stays the end further up-left
+        let curve = layout.place_block((5, 0), BlockKind::Curve(Port::West)).unwrap();
+        let up = layout.place_block((5, -1), BlockKind::Straight(Direction::Vertical)).unwrap();
+        let group = &layout.groups[&group_id];
+        assert_eq!(group.direction, None);
+        assert_eq!(group.blocks.len(), 8);
+        assert!(group.is_endpoint(up) && !group.is_endpoint(curve));
+        assert_ordered(&layout, group_id);
+    }
+
+    fn endpoint(layout: &Layout, block: BlockId) -> Endpoint {
+        layout.endpoint_of_block(block).unwrap()
+    }
+
+    #[test]
+    fn splitting_keeps_connections_on_their_end_blocks() {
+        let mut layout = Layout::default();
+        let run = layout.lay_run((0, 0), (4, 0)).unwrap();
+        let other = layout.lay_run((0, 2), (4, 2)).unwrap();
+        layout.connect_groups(run[0], other[0], false).unwrap();
+        layout.connect_groups(run[4], other[4], false).unwrap();
+
+        assert_eq!(layout.remove_blocks(&[run[2]]).unwrap(), 1);
+        assert_eq!(layout.groups.len(), 3);
+        assert_ne!(layout.group_of_block(run[0]).unwrap().id, layout.group_of_block(run[4]).unwrap().id);
+        for (block, partner) in [(run[0], other[0]), (run[4], other[4])] {
+            let connection = layout.connections.at(endpoint(&layout, block)).unwrap();
+            assert_eq!(connection.other(endpoint(&layout, block)), Some(endpoint(&layout, partner)));
+        }
+        // The new ends where the block was cut out are free
+        assert!(layout.attached_to(endpoint(&layout, run[1])).is_none());
+        assert!(layout.validate().is_empty());
+    }
+
+    #[test]
+    fn connections_of_removed_end_blocks_are_dropped() {
+        let mut layout = Layout::default();
+        let run = layout.lay_run((0, 0), (4, 0)).unwrap();
+        let other = layout.lay_run((0, 2), (4, 2)).unwrap();
+        layout.connect_groups(run[0], other[0], false).unwrap();
+        layout.connect_groups(run[4], other[4], false).unwrap();
+
+        layout.remove_blocks(&[run[4]]).unwrap();
+        assert_eq!(layout.connections.len(), 1);
+        assert!(layout.connections.at(endpoint(&layout, run[0])).is_some());
+        assert!(layout.attached_to(endpoint(&layout, other[4])).is_none());
+    }
+
+    #[test]
+    fn auto_placed_straights_continue_their_neighbors() {
+        let mut layout = Layout::default();
+        let vertical = BlockKind::Straight(Direction::Vertical);
+        layout.lay_run((0, 0), (0, 2)).unwrap();
+        layout.place_block_auto((0, 3)).unwrap();
+        assert_eq!(layout.block_at((0, 3)).unwrap().1.kind, vertical);
+
+        // A lone block has no direction yet and turns to face the next one
+        let first = layout.place_block_auto((5, 5)).unwrap();
+        assert_eq!(layout.block_at((5, 5)).unwrap().1.kind, HORIZONTAL);
+        let second = layout.place_block_auto((6, 6)).unwrap();
+        let diagonal = BlockKind::Straight(Direction::DiagonalDown);
+        assert_eq!(layout.block_at((5, 5)).unwrap().1.kind, diagonal);
+        assert_eq!(layout.block_at((6, 6)).unwrap().1.kind, diagonal);
+        let group = layout.group_of_block(first).unwrap();
+        assert!(group.is_endpoint(first) && group.is_endpoint(second));
+        assert_eq!(group.direction, Some(Direction::DiagonalDown));
+    }
+
+    #[test]
+    fn an_endpoint_takes_one_connection_or_turnout() {
+        let mut layout = Layout::default();
+        let [a, b, c, d] = [0, 2, 4, 6].map(|y| layout.lay_run((0, y), (2, y)).unwrap());
+        layout.connect_groups(a[2], b[2], false).unwrap();
+
+        assert!(matches!(layout.connect_groups(a[2], b[2], false), Err(LayoutError::ConnectionExists)));
+        assert!(matches!(layout.connect_groups(c[2], a[2], false), Err(LayoutError::EndpointInUse(_))));
+        assert!(matches!(layout.connect_groups(c[1], d[2], false), Err(LayoutError::NotAnEndpoint(_))));
+        assert!(matches!(layout.add_turnout(a[2], c[0], d[0]), Err(LayoutError::EndpointInUse(_))));
+
+        layout.add_turnout(c[2], d[0], d[2]).unwrap();
+        assert!(matches!(layout.connect_groups(b[0], d[0], false), Err(LayoutError::EndpointInUse(_))));
+        assert_eq!(layout.connections.len(), 1);
+    }
+
+    #[test]
+    fn moved_track_merges_with_the_open_end_it_meets() {
+        let mut layout = Layout::default();
+        let run = layout.lay_run((0, 0), (2, 0)).unwrap();
+        let lone = layout.place_block((6, 3), HORIZONTAL).unwrap();
+        assert_eq!(layout.groups.len(), 2);
+
+        assert!(matches!(layout.can_move_blocks(&[lone], (-4, -3)), Err(LayoutError::PositionOccupied((2, 0)))));
+        layout.move_blocks(&[lone], (-3, -3)).unwrap();
+        assert_eq!(layout.groups.len(), 1);
+        let group_id = layout.group_of_block(run[0]).unwrap().id;
+        assert_eq!(layout.groups[&group_id].end_block_id, Some(lone));
+        assert_ordered(&layout, group_id);
+
+        // Moving the middle out of line leaves three pieces
+        layout.move_blocks(&[run[1]], (0, 2)).unwrap();
+        assert_eq!(layout.groups.len(), 3);
+        for group_id in layout.groups.keys() {
+            assert_ordered(&layout, *group_id);
+        }
+        assert!(layout.validate().is_empty());
+    }
+
+    #[test]
+    fn rotating_and_mirroring_turn_blocks_within_their_bounds() {
+        let mut layout = Layout::default();
+        let run = layout.lay_run((0, 0), (2, 0)).unwrap();
+        let group_id = layout.group_of_block(run[0]).unwrap().id;
+
+        layout.transform_blocks(&run, Transform::RotateCw).unwrap();
+        let positions: Vec<_> = run.iter().map(|id| layout.block(*id).unwrap().grid_pos).collect();
+        assert_eq!(positions, [(0, 0), (0, 1), (0, 2)]);
+        assert_eq!(layout.groups[&group_id].direction, Some(Direction::Vertical));
+        assert_ordered(&layout, group_id);
+
+        // Mirroring top and bottom turns the run around, so its start and end swap blocks
+        layout.transform_blocks(&run, Transform::MirrorVertical).unwrap();
+        assert_eq!(layout.block(run[0]).unwrap().grid_pos, (0, 2));
+        assert_eq!(layout.groups[&group_id].start_block_id, Some(run[2]));
+        assert_ordered(&layout, group_id);
+
+        let curve = layout.place_block((5, 5), BlockKind::Curve(Port::West)).unwrap();
+        layout.transform_blocks(&[curve], Transform::MirrorHorizontal).unwrap();
+        assert_eq!(layout.block(curve).unwrap().kind, BlockKind::Curve(Port::North));
+    }
+
+    #[test]
+    fn corner_ends_are_suggested_once() {
+        let mut layout = Layout::default();
+        let across = layout.lay_run((0, 0), (2, 0)).unwrap();
+        let down = layout.lay_run((3, 1), (3, 3)).unwrap();
+        // Too far from either end
+        layout.place_block((8, 8), HORIZONTAL).unwrap();
+        assert_eq!(layout.groups.len(), 3);
+
+        let suggestions = layout.suggest_connections();
+        assert_eq!(suggestions, [(endpoint(&layout, across[2]), endpoint(&layout, down[0]))]);
+
+        let (from, to) = suggestions[0];
+        layout.connect_endpoints(from, to, false).unwrap();
+        assert!(layout.suggest_connections().is_empty());
+    }
+
+    #[test]
+    fn split_at_a_block_gives_the_rest_a_new_group() {
+        let mut layout = Layout::default();
+        let run = layout.lay_run((0, 0), (3, 0)).unwrap();
+        let group_id = layout.group_of_block(run[0]).unwrap().id;
+        layout.rename_group(group_id, Some("Main".to_string())).unwrap();
+
+        let new_id = layout.split_group_at(run[1]).unwrap();
+        assert_eq!(layout.groups[&group_id].blocks.len(), 2);
+        assert_eq!(layout.groups[&group_id].name.as_deref(), Some("Main"));
+        assert_eq!(layout.groups[&new_id].start_block_id, Some(run[2]));
+        assert_eq!(layout.groups[&new_id].name, None);
+        assert_ordered(&layout, group_id);
+        assert_ordered(&layout, new_id);
+    }
+
+    #[test]
+    fn compacting_closes_id_gaps_and_remaps_what_refers_to_them() {
+        let mut layout = Layout::default();
+        // Group 1 and its blocks, connection 1 and turnout 1 are removed again to leave gaps
+        let gap = layout.lay_run((0, 0), (1, 0)).unwrap();
+        let main = layout.lay_run((0, 2), (2, 2)).unwrap();
+        let branch = layout.lay_run((4, 2), (4, 4)).unwrap();
+        let siding = layout.lay_run((6, 6), (8, 6)).unwrap();
+        let removed = layout.connect_groups(main[0], siding[0], false).unwrap();
+        layout.remove_connection(removed).unwrap();
+        let connection = layout.connect_groups(main[2], branch[0], true).unwrap();
+        let removed = layout.add_turnout(branch[2], siding[0], siding[2]).unwrap();
+        layout.remove_turnout(removed).unwrap();
+        let turnout = layout.add_turnout(branch[2], siding[0], siding[2]).unwrap();
+        layout.remove_blocks(&gap).unwrap();
+
+        let mapping = layout.compact_ids();
+        assert_eq!(mapping.groups, HashMap::from([(GroupId(2), GroupId(1)), (GroupId(3), GroupId(2)), (GroupId(4), GroupId(3))]));
+        assert_eq!(mapping.blocks, (3..=11).map(|id| (BlockId(id), BlockId(id - 2))).collect());
+        assert_eq!(mapping.connections, HashMap::from([(connection, ConnectionId(1))]));
+        assert_eq!(mapping.turnouts, HashMap::from([(turnout, TurnoutId(1))]));
+
+        let ends = |group: u32| {
+            let group = &layout.groups[&GroupId(group)];
+            (group.start_block_id, group.end_block_id)
+        };
+        assert_eq!(ends(1), (Some(BlockId(1)), Some(BlockId(3))));
+        assert_eq!(ends(2), (Some(BlockId(4)), Some(BlockId(6))));
+        assert_eq!(ends(3), (Some(BlockId(7)), Some(BlockId(9))));
+
+        let end = |group: u32, end: ConnectionType| Endpoint { group: GroupId(group), end };
+        let connection = layout.connections.get(ConnectionId(1)).unwrap();
+        assert_eq!((connection.from, connection.to), (end(1, ConnectionType::End), end(2, ConnectionType::Start)));
+        let turnout = layout.turnouts.get(TurnoutId(1)).unwrap();
+        assert_eq!(turnout.points, end(2, ConnectionType::End));
+        assert_eq!((turnout.normal, turnout.reverse), (end(3, ConnectionType::Start), end(3, ConnectionType::End)));
+
+        assert_eq!((layout.next_group_id, layout.next_block_id), (4, 10));
+        assert_eq!((layout.next_connection_id, layout.next_turnout_id), (2, 2));
+        assert!(layout.validate().is_empty());
+    }
+
+    #[test]
+    fn groups_are_not_joined_at_a_turnout() {
+        let mut layout = Layout::default();
+        let a = layout.lay_run((0, 0), (2, 0)).unwrap();
+        let b = layout.lay_run((4, 0), (6, 0)).unwrap();
+        let c = layout.lay_run((4, 2), (6, 2)).unwrap();
+        layout.add_turnout(a[2], b[0], c[0]).unwrap();
+        let joined_ends = [endpoint(&layout, a[2]), endpoint(&layout, b[0])];
+
+        let result = layout.place_block((3, 0), HORIZONTAL);
+        assert!(matches!(result, Err(LayoutError::EndpointInUse(end)) if joined_ends.contains(&end)));
+        assert!(!layout.is_occupied((3, 0)));
+        assert_eq!((layout.groups.len(), layout.turnouts.len()), (3, 1));
+
+        // Without the turnout the same block joins the two groups
+        layout.remove_turnout(TurnoutId(1)).unwrap();
+        layout.place_block((3, 0), HORIZONTAL).unwrap();
+        assert_eq!(layout.groups.len(), 2);
+        assert!(layout.validate().is_empty());
+    }
+}
@@ -0,0 +1,28 @@
+// Headless railway layout model.
+// Everything needed to load, edit, check and save a layout without any GUI dependency;
+// the egui editor is a frontend over this crate.
+
+mod change;
+pub mod error;
+pub mod format;
+pub mod fragment;
+pub mod group_cache;
+pub mod history;
+mod index;
+pub mod layout;
+pub mod models;
+pub mod route;
+pub mod table;
+pub mod validate;
+
+pub use error::LayoutError;
+pub use fragment::{Fragment, Pasted};
+pub use group_cache::GroupCache;
+pub use history::{Command, History, Outcome};
+pub use layout::{Attachment, IdMapping, Layout};
+pub use models::{
+    Block, BlockId, BlockKind, Connection, ConnectionId, ConnectionType, Direction, Endpoint, Group, GroupId,
+    Hand, Port, Transform, Turnout, TurnoutId, TurnoutPosition,
+};
+pub use table::{Attached, EndpointTable};
+pub use validate::{Diagnostic, Problem, Severity};
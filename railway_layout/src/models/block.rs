@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+use super::{BlockId, BlockKind, Port};
+
+// MARK: - Block
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Block {
+    pub id: BlockId,
+    pub grid_pos: (i32, i32),
+    #[serde(default)]
+    pub kind: BlockKind,
+}
+
+// MARK: - Block Implementation
+impl Block {
+    // The port of this block that leads into the given neighboring cell, if any
+    pub fn port_towards(&self, pos: (i32, i32)) -> Option<Port> {
+        let offset = (pos.0 - self.grid_pos.0, pos.1 - self.grid_pos.1);
+        Port::from_offset(offset).filter(|port| self.kind.has_port(*port))
+    }
+
+    // Two blocks are linked when they are neighbors and their track ends meet
+    pub fn links_to(&self, other: &Block) -> bool {
+        match self.port_towards(other.grid_pos) {
+            Some(port) => other.kind.has_port(port.opposite()),
+            None => false,
+        }
+    }
+}
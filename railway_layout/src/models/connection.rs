@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use super::{ConnectionId, GroupId};
+use crate::table::Attached;
+
+// MARK: - Connection
+// Links two group endpoints. A directed connection can only be travelled from `from` to `to`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Connection {
+    pub id: ConnectionId,
+    pub from: Endpoint,
+    pub to: Endpoint,
+    pub directed: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum ConnectionType {
+    Start,
+    End,
+}
+
+impl ConnectionType {
+    pub fn opposite(self) -> ConnectionType {
+        match self {
+            ConnectionType::Start => ConnectionType::End,
+            ConnectionType::End => ConnectionType::Start,
+        }
+    }
+}
+
+// MARK: - Endpoint
+// The start or end of a group
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Endpoint {
+    pub group: GroupId,
+    pub end: ConnectionType,
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.end {
+            ConnectionType::Start => write!(f, "start of group {}", self.group),
+            ConnectionType::End => write!(f, "end of group {}", self.group),
+        }
+    }
+}
+
+// MARK: - Connection Implementation
+impl Connection {
+    // The endpoint on the other side of the connection
+    pub fn other(&self, endpoint: Endpoint) -> Option<Endpoint> {
+        if endpoint == self.from {
+            Some(self.to)
+        } else if endpoint == self.to {
+            Some(self.from)
+        } else {
+            None
+        }
+    }
+
+    // Where a train leaving its group through `endpoint` continues, respecting the direction
+    pub fn next_from(&self, endpoint: Endpoint) -> Option<Endpoint> {
+        if endpoint == self.from {
+            Some(self.to)
+        } else if endpoint == self.to && !self.directed {
+            Some(self.from)
+        } else {
+            None
+        }
+    }
+}
+
+impl Attached for Connection {
+    type Id = ConnectionId;
+
+    fn id(&self) -> ConnectionId {
+        self.id
+    }
+
+    fn endpoints(&self) -> Vec<Endpoint> {
+        vec![self.from, self.to]
+    }
+
+    fn endpoints_mut(&mut self) -> Vec<&mut Endpoint> {
+        vec![&mut self.from, &mut self.to]
+    }
+}
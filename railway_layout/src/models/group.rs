@@ -0,0 +1,223 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use super::{block::Block, BlockId, BlockKind, GroupId, Port};
+
+// MARK: - Direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Direction {
+    Horizontal,
+    Vertical,
+    // From bottom-left to top-right
+    DiagonalUp,
+    // From top-left to bottom-right
+    DiagonalDown,
+}
+
+impl Direction {
+    pub fn is_diagonal(self) -> bool {
+        matches!(self, Direction::DiagonalUp | Direction::DiagonalDown)
+    }
+}
+
+// MARK: - Group
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Group {
+    pub id: GroupId,
+    pub blocks: Vec<Block>,
+    pub direction: Option<Direction>,
+    pub start_block_id: Option<BlockId>,
+    pub end_block_id: Option<BlockId>,
+    // Shown instead of the id where set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    // The start is the end with the larger grid position instead of the smaller one
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub reversed: bool,
+}
+
+// MARK: - Group Implementation
+impl Group {
+    // Order the blocks along the track they form and pick the start and end block.
+    // Of the two ends of the run the start is the one with the smaller grid position,
+    // i.e. the leftmost block of a horizontal group and the topmost block of a vertical one,
+    // unless the group is reversed.
+    pub fn update_start_end_blocks(&mut self) {
+        if self.blocks.is_empty() {
+            self.start_block_id = None;
+            self.end_block_id = None;
+            return;
+        }
+
+        self.blocks = order_along_track(std::mem::take(&mut self.blocks));
+        if self.reversed {
+            self.blocks.reverse();
+        }
+        self.start_block_id = Some(self.blocks[0].id);
+        self.end_block_id = Some(self.blocks.last().unwrap().id);
+    }
+
+    // A group has a direction when all of its blocks are straights along the same axis
+    pub fn update_direction(&mut self) {
+        let first = match self.blocks.first() {
+            Some(Block { kind: BlockKind::Straight(direction), .. }) if self.blocks.len() >= 2 => *direction,
+            _ => {
+                self.direction = None;
+                return;
+            }
+        };
+
+        let straight = self.blocks.iter().all(|block| block.kind == BlockKind::Straight(first));
+        self.direction = if straight { Some(first) } else { None };
+    }
+
+    // The name if it has one, otherwise the id
+    pub fn display_name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| format!("Group {}", self.id))
+    }
+
+    pub fn is_endpoint(&self, block_id: BlockId) -> bool {
+        self.start_block_id == Some(block_id) || self.end_block_id == Some(block_id)
+    }
+
+    // Whether the track can be continued from the given endpoint block through `port`,
+    // i.e. the block's piece has a route from its inner neighbor to that port
+    pub fn can_extend(&self, block_id: BlockId, port: Port) -> bool {
+        if !self.is_endpoint(block_id) {
+            return false;
+        }
+        // Ends are the first and the last block
+        let index = if self.blocks.first().is_some_and(|block| block.id == block_id) {
+            0
+        } else if self.blocks.last().is_some_and(|block| block.id == block_id) {
+            self.blocks.len() - 1
+        } else {
+            return false;
+        };
+        let block = &self.blocks[index];
+        if !block.kind.has_port(port) {
+            return false;
+        }
+        if self.blocks.len() == 1 {
+            return true;
+        }
+
+        let inner = if index == 0 { &self.blocks[1] } else { &self.blocks[self.blocks.len() - 2] };
+        match block.port_towards(inner.grid_pos) {
+            Some(inner_port) => inner_port != port && block.kind.has_route(inner_port, port),
+            None => false,
+        }
+    }
+}
+
+// Walk the blocks along their track links, starting from the end with the smaller grid position.
+// Blocks that cannot be reached from there are appended at the end so none are lost.
+fn order_along_track(blocks: Vec<Block>) -> Vec<Block> {
+    let positions: HashMap<(i32, i32), usize> = blocks.iter()
+        .enumerate()
+        .map(|(i, block)| (block.grid_pos, i))
+        .collect();
+    let links = |i: usize| -> Vec<usize> {
+        blocks[i].kind.ports().into_iter()
+            .filter_map(|port| {
+                let (dx, dy) = port.offset();
+                let (x, y) = blocks[i].grid_pos;
+                positions.get(&(x + dx, y + dy)).copied()
+            })
+            .filter(|&j| blocks[i].links_to(&blocks[j]))
+            .collect()
+    };
+
+    // Prefer a real end of the run; a closed loop has none, so any block will do
+    let start = (0..blocks.len())
+        .filter(|&i| links(i).len() <= 1)
+        .min_by_key(|&i| blocks[i].grid_pos)
+        .or_else(|| (0..blocks.len()).min_by_key(|&i| blocks[i].grid_pos))
+        .unwrap_or(0);
+
+    let mut visited = vec![false; blocks.len()];
+    let mut order = vec![start];
+    visited[start] = true;
+    let mut previous: Option<usize> = None;
+    let mut current = start;
+
+    loop {
+        let block = &blocks[current];
+        let entry = previous.and_then(|p| block.port_towards(blocks[p].grid_pos));
+        let candidates: Vec<usize> = links(current).into_iter().filter(|&j| !visited[j]).collect();
+        // Follow a route through the current piece when there is a choice
+        let next = candidates.iter()
+            .copied()
+            .min_by_key(|&j| {
+                let exit = block.port_towards(blocks[j].grid_pos);
+                let routed = match (entry, exit) {
+                    (Some(entry), Some(exit)) => block.kind.has_route(entry, exit),
+                    _ => true,
+                };
+                (!routed, blocks[j].grid_pos)
+            });
+
+        match next {
+            Some(next) => {
+                visited[next] = true;
+                order.push(next);
+                previous = Some(current);
+                current = next;
+            }
+            None => break,
+        }
+    }
+
+    order.extend((0..blocks.len()).filter(|&i| !visited[i]));
+    let mut slots: Vec<Option<Block>> = blocks.into_iter().map(Some).collect();
+    order.into_iter().filter_map(|i| slots[i].take()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(cells: &[((i32, i32), BlockKind)]) -> Group {
+        let blocks = cells.iter().enumerate()
+            .map(|(i, (grid_pos, kind))| Block { id: BlockId(i as u32 + 1), grid_pos: *grid_pos, kind: *kind })
+            .collect();
+        Group { id: GroupId(1), blocks, direction: None, start_block_id: None, end_block_id: None, name: None, reversed: false }
+    }
+
+    #[test]
+    fn blocks_are_ordered_along_the_track_from_the_smaller_end() {
+        let vertical = BlockKind::Straight(Direction::Vertical);
+        // An L, given out of order: down from (2, -2), then left from the corner
+        let mut group = group(&[
+            ((0, 0), BlockKind::Straight(Direction::Horizontal)),
+            ((2, -1), vertical),
+            ((2, 0), BlockKind::Curve(Port::West)),
+            ((2, -2), vertical),
+            ((1, 0), BlockKind::Straight(Direction::Horizontal)),
+        ]);
+        group.update_start_end_blocks();
+        group.update_direction();
+
+        let order: Vec<u32> = group.blocks.iter().map(|block| block.id.0).collect();
+        assert_eq!(order, [1, 5, 3, 2, 4]);
+        assert_eq!((group.start_block_id, group.end_block_id), (Some(BlockId(1)), Some(BlockId(4))));
+        assert_eq!(group.direction, None);
+
+        group.reversed = true;
+        group.update_start_end_blocks();
+        assert_eq!((group.start_block_id, group.end_block_id), (Some(BlockId(4)), Some(BlockId(1))));
+    }
+
+    #[test]
+    fn only_open_ends_can_be_extended() {
+        let horizontal = BlockKind::Straight(Direction::Horizontal);
+        let mut group = group(&[((0, 0), horizontal), ((1, 0), horizontal), ((2, 0), horizontal)]);
+        group.update_start_end_blocks();
+
+        assert!(group.can_extend(BlockId(3), Port::East));
+        assert!(group.can_extend(BlockId(1), Port::West));
+        // Back into the group, off the axis, or from the middle
+        assert!(!group.can_extend(BlockId(3), Port::West));
+        assert!(!group.can_extend(BlockId(3), Port::North));
+        assert!(!group.can_extend(BlockId(2), Port::North));
+    }
+}
@@ -0,0 +1,13 @@
+pub mod block;
+pub mod connection;
+pub mod group;
+pub mod ids;
+pub mod track;
+pub mod turnout;
+
+pub use block::Block;
+pub use connection::{Connection, ConnectionType, Endpoint};
+pub use group::{Group, Direction};
+pub use ids::{BlockId, ConnectionId, GroupId, TurnoutId};
+pub use track::{BlockKind, Hand, Port, Transform};
+pub use turnout::{Turnout, TurnoutPosition};
\ No newline at end of file
@@ -0,0 +1,295 @@
+use serde::{Deserialize, Serialize};
+use super::Direction;
+
+// MARK: - Port
+// Side or corner of a cell through which a piece of track leaves it.
+// Grid y grows downwards, so North is (0, -1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Port {
+    North,
+    NorthEast,
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
+}
+
+impl Port {
+    // Clockwise, in 45° steps
+    pub const ALL: [Port; 8] = [
+        Port::North,
+        Port::NorthEast,
+        Port::East,
+        Port::SouthEast,
+        Port::South,
+        Port::SouthWest,
+        Port::West,
+        Port::NorthWest,
+    ];
+
+    pub fn offset(self) -> (i32, i32) {
+        match self {
+            Port::North => (0, -1),
+            Port::NorthEast => (1, -1),
+            Port::East => (1, 0),
+            Port::SouthEast => (1, 1),
+            Port::South => (0, 1),
+            Port::SouthWest => (-1, 1),
+            Port::West => (-1, 0),
+            Port::NorthWest => (-1, -1),
+        }
+    }
+
+    pub fn from_offset(offset: (i32, i32)) -> Option<Port> {
+        Port::ALL.into_iter().find(|port| port.offset() == offset)
+    }
+
+    pub fn is_diagonal(self) -> bool {
+        let (dx, dy) = self.offset();
+        dx != 0 && dy != 0
+    }
+
+    // Turn clockwise by the given number of 45° steps
+    pub fn rotated(self, steps: i32) -> Port {
+        let index = Port::ALL.iter().position(|port| *port == self).unwrap() as i32;
+        Port::ALL[(index + steps).rem_euclid(8) as usize]
+    }
+
+    pub fn opposite(self) -> Port {
+        self.rotated(4)
+    }
+
+    pub fn transformed(self, transform: Transform) -> Port {
+        let (dx, dy) = self.offset();
+        let offset = match transform {
+            Transform::RotateCw => (-dy, dx),
+            Transform::RotateCcw => (dy, -dx),
+            Transform::MirrorHorizontal => (-dx, dy),
+            Transform::MirrorVertical => (dx, -dy),
+        };
+        Port::from_offset(offset).unwrap()
+    }
+
+    // Axis of a straight piece of track leaving through this port
+    pub fn direction(self) -> Direction {
+        match self {
+            Port::North | Port::South => Direction::Vertical,
+            Port::East | Port::West => Direction::Horizontal,
+            Port::NorthEast | Port::SouthWest => Direction::DiagonalUp,
+            Port::NorthWest | Port::SouthEast => Direction::DiagonalDown,
+        }
+    }
+}
+
+// MARK: - Transform
+// Quarter turns and mirror images, as applied to a selection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Transform {
+    RotateCw,
+    RotateCcw,
+    // Left and right swap places
+    MirrorHorizontal,
+    // Top and bottom swap places
+    MirrorVertical,
+}
+
+impl Transform {
+    // Mirroring turns clockwise into counter-clockwise
+    pub fn is_reflection(self) -> bool {
+        matches!(self, Transform::MirrorHorizontal | Transform::MirrorVertical)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Transform::RotateCw => "Rotate clockwise",
+            Transform::RotateCcw => "Rotate counter-clockwise",
+            Transform::MirrorHorizontal => "Mirror horizontally",
+            Transform::MirrorVertical => "Mirror vertically",
+        }
+    }
+}
+
+// MARK: - Hand
+// Side the diverging (reverse) leg of a turnout branches off to, seen from the points
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Hand {
+    Left,
+    Right,
+}
+
+// MARK: - BlockKind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BlockKind {
+    Straight(Direction),
+    // 90° curve joining the given port and the port clockwise from it
+    Curve(Port),
+    // `points` is the side the switch blades face; normal continues straight through,
+    // reverse branches off by 45° to the `hand` side
+    Turnout { points: Port, hand: Hand },
+    // Diamond crossing of a horizontal and a vertical track
+    Crossing,
+    // End of track; the track continues through the given port only
+    BufferStop(Port),
+}
+
+impl Default for BlockKind {
+    fn default() -> Self {
+        BlockKind::Straight(Direction::Horizontal)
+    }
+}
+
+impl Hand {
+    pub fn opposite(self) -> Hand {
+        match self {
+            Hand::Left => Hand::Right,
+            Hand::Right => Hand::Left,
+        }
+    }
+}
+
+impl BlockKind {
+    pub fn ports(&self) -> Vec<Port> {
+        match *self {
+            BlockKind::Straight(direction) => {
+                let (a, b) = straight_ports(direction);
+                vec![a, b]
+            }
+            BlockKind::Curve(port) => vec![port, port.transformed(Transform::RotateCw)],
+            BlockKind::Turnout { points, hand } => {
+                vec![points, points.opposite(), reverse_port(points, hand)]
+            }
+            BlockKind::Crossing => vec![Port::North, Port::East, Port::South, Port::West],
+            BlockKind::BufferStop(port) => vec![port],
+        }
+    }
+
+    // Pairs of ports that a train can pass between
+    pub fn routes(&self) -> Vec<(Port, Port)> {
+        match *self {
+            BlockKind::Straight(direction) => vec![straight_ports(direction)],
+            BlockKind::Curve(port) => vec![(port, port.transformed(Transform::RotateCw))],
+            BlockKind::Turnout { points, hand } => vec![
+                (points, points.opposite()),
+                (points, reverse_port(points, hand)),
+            ],
+            BlockKind::Crossing => vec![(Port::North, Port::South), (Port::West, Port::East)],
+            BlockKind::BufferStop(_) => vec![],
+        }
+    }
+
+    pub fn has_port(&self, port: Port) -> bool {
+        self.ports().contains(&port)
+    }
+
+    pub fn has_route(&self, a: Port, b: Port) -> bool {
+        self.routes().iter().any(|&(x, y)| (x == a && y == b) || (x == b && y == a))
+    }
+
+    pub fn transformed(&self, transform: Transform) -> BlockKind {
+        match *self {
+            BlockKind::Straight(direction) => {
+                let (port, _) = straight_ports(direction);
+                BlockKind::Straight(port.transformed(transform).direction())
+            }
+            // A mirrored curve runs the other way round, so it starts from the other port
+            BlockKind::Curve(port) if transform.is_reflection() => {
+                BlockKind::Curve(port.transformed(transform).transformed(Transform::RotateCcw))
+            }
+            BlockKind::Curve(port) => BlockKind::Curve(port.transformed(transform)),
+            BlockKind::Turnout { points, hand } => BlockKind::Turnout {
+                points: points.transformed(transform),
+                hand: if transform.is_reflection() { hand.opposite() } else { hand },
+            },
+            BlockKind::Crossing => BlockKind::Crossing,
+            BlockKind::BufferStop(port) => BlockKind::BufferStop(port.transformed(transform)),
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            BlockKind::Straight(direction) if direction.is_diagonal() => "Diagonal",
+            BlockKind::Straight(_) => "Straight",
+            BlockKind::Curve(_) => "Curve",
+            BlockKind::Turnout { hand: Hand::Left, .. } => "Left turnout",
+            BlockKind::Turnout { hand: Hand::Right, .. } => "Right turnout",
+            BlockKind::Crossing => "Crossing",
+            BlockKind::BufferStop(_) => "Buffer stop",
+        }
+    }
+}
+
+fn straight_ports(direction: Direction) -> (Port, Port) {
+    match direction {
+        Direction::Horizontal => (Port::West, Port::East),
+        Direction::Vertical => (Port::North, Port::South),
+        Direction::DiagonalUp => (Port::SouthWest, Port::NorthEast),
+        Direction::DiagonalDown => (Port::NorthWest, Port::SouthEast),
+    }
+}
+
+// Travelling from the points into the turnout, the reverse leg leaves to the left or right
+fn reverse_port(points: Port, hand: Hand) -> Port {
+    let heading = points.opposite();
+    match hand {
+        Hand::Left => heading.rotated(-1),
+        Hand::Right => heading.rotated(1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRANSFORMS: [Transform; 4] = [Transform::RotateCw, Transform::RotateCcw, Transform::MirrorHorizontal, Transform::MirrorVertical];
+
+    fn kinds() -> Vec<BlockKind> {
+        let mut kinds = vec![BlockKind::Crossing];
+        kinds.extend([Direction::Horizontal, Direction::Vertical, Direction::DiagonalUp, Direction::DiagonalDown].map(BlockKind::Straight));
+        for port in Port::ALL {
+            kinds.extend([BlockKind::Curve(port), BlockKind::BufferStop(port)]);
+            kinds.extend([Hand::Left, Hand::Right].map(|hand| BlockKind::Turnout { points: port, hand }));
+        }
+        kinds
+    }
+
+    fn sorted(mut routes: Vec<(Port, Port)>) -> Vec<(Port, Port)> {
+        for route in &mut routes {
+            if Port::ALL.iter().position(|p| *p == route.0) > Port::ALL.iter().position(|p| *p == route.1) {
+                *route = (route.1, route.0);
+            }
+        }
+        routes.sort_by_key(|(a, b)| (*a as u8, *b as u8));
+        routes
+    }
+
+    #[test]
+    fn transformed_pieces_route_between_the_transformed_ports() {
+        for kind in kinds() {
+            for transform in TRANSFORMS {
+                let expected = kind.routes().into_iter().map(|(a, b)| (a.transformed(transform), b.transformed(transform))).collect();
+                assert_eq!(sorted(kind.transformed(transform).routes()), sorted(expected), "{kind:?} {transform:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn four_quarter_turns_or_two_mirrors_give_the_same_piece() {
+        for kind in kinds() {
+            let turned = (0..4).fold(kind, |kind, _| kind.transformed(Transform::RotateCw));
+            assert_eq!(turned, kind);
+            for mirror in [Transform::MirrorHorizontal, Transform::MirrorVertical] {
+                assert_eq!(kind.transformed(mirror).transformed(mirror), kind);
+            }
+            assert_eq!(kind.transformed(Transform::RotateCw).transformed(Transform::RotateCcw), kind);
+        }
+    }
+
+    #[test]
+    fn mirrored_turnouts_change_hand() {
+        let turnout = BlockKind::Turnout { points: Port::West, hand: Hand::Left };
+        assert_eq!(turnout.transformed(Transform::MirrorVertical), BlockKind::Turnout { points: Port::West, hand: Hand::Right });
+        assert_eq!(turnout.transformed(Transform::RotateCw), BlockKind::Turnout { points: Port::North, hand: Hand::Left });
+    }
+}
@@ -0,0 +1,68 @@
+use serde::{Deserialize, Serialize};
+use super::{Endpoint, TurnoutId};
+use crate::table::Attached;
+
+// MARK: - TurnoutPosition
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TurnoutPosition {
+    Normal,
+    Reverse,
+}
+
+impl TurnoutPosition {
+    pub fn toggled(self) -> TurnoutPosition {
+        match self {
+            TurnoutPosition::Normal => TurnoutPosition::Reverse,
+            TurnoutPosition::Reverse => TurnoutPosition::Normal,
+        }
+    }
+}
+
+// MARK: - Turnout
+// A switch joining the `points` endpoint to either the `normal` or the `reverse` endpoint,
+// depending on its current position. Only the active leg counts as connected.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Turnout {
+    pub id: TurnoutId,
+    pub points: Endpoint,
+    pub normal: Endpoint,
+    pub reverse: Endpoint,
+    pub position: TurnoutPosition,
+}
+
+// MARK: - Turnout Implementation
+impl Turnout {
+    pub fn active_leg(&self) -> Endpoint {
+        match self.position {
+            TurnoutPosition::Normal => self.normal,
+            TurnoutPosition::Reverse => self.reverse,
+        }
+    }
+
+    pub fn inactive_leg(&self) -> Endpoint {
+        match self.position {
+            TurnoutPosition::Normal => self.reverse,
+            TurnoutPosition::Reverse => self.normal,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.position = self.position.toggled();
+    }
+}
+
+impl Attached for Turnout {
+    type Id = TurnoutId;
+
+    fn id(&self) -> TurnoutId {
+        self.id
+    }
+
+    fn endpoints(&self) -> Vec<Endpoint> {
+        vec![self.points, self.normal, self.reverse]
+    }
+
+    fn endpoints_mut(&mut self) -> Vec<&mut Endpoint> {
+        vec![&mut self.points, &mut self.normal, &mut self.reverse]
+    }
+}
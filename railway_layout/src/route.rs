@@ -0,0 +1,166 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use crate::layout::Layout;
+use crate::models::{ConnectionType, Endpoint, GroupId};
+
+// Everything that follows the track from one group to the next goes through here,
+// so turnouts only ever count with their active leg.
+
+// MARK: - Links
+impl Layout {
+    // Every pair of endpoints that is currently joined: all connections plus the active leg of each turnout
+    pub fn active_links(&self) -> Vec<(Endpoint, Endpoint)> {
+        let connections = self.connections.iter().map(|conn| (conn.from, conn.to));
+        let turnouts = self.turnouts.iter().map(|turnout| (turnout.points, turnout.active_leg()));
+        connections.chain(turnouts).collect()
+    }
+
+    // Endpoints a train can continue to after leaving a group through `endpoint`.
+    // Directed connections are only followed forwards.
+    pub fn linked_endpoints(&self, endpoint: Endpoint) -> Vec<Endpoint> {
+        if let Some(conn) = self.connections.at(endpoint) {
+            return conn.next_from(endpoint).into_iter().collect();
+        }
+        let Some(turnout) = self.turnouts.at(endpoint) else {
+            return vec![];
+        };
+        if endpoint == turnout.points {
+            vec![turnout.active_leg()]
+        } else if endpoint == turnout.active_leg() {
+            vec![turnout.points]
+        } else {
+            vec![]
+        }
+    }
+}
+
+// MARK: - Reachability
+impl Layout {
+    // Every group reachable from the given ones through connections and turnouts, including
+    // the given groups themselves. Direction and turnout positions are ignored, since this is
+    // about which track belongs together rather than where a train can go.
+    pub fn connected_groups(&self, start: &[GroupId]) -> Vec<GroupId> {
+        let mut seen: HashSet<GroupId> = HashSet::new();
+        let mut result = vec![];
+        let mut queue: VecDeque<GroupId> = start.iter()
+            .copied()
+            .filter(|id| self.groups.contains_key(id))
+            .collect();
+
+        while let Some(group_id) = queue.pop_front() {
+            if !seen.insert(group_id) {
+                continue;
+            }
+            result.push(group_id);
+
+            for end in [ConnectionType::Start, ConnectionType::End] {
+                let endpoint = Endpoint { group: group_id, end };
+                let mut neighbors = vec![];
+                if let Some(conn) = self.connections.at(endpoint) {
+                    neighbors.extend(conn.other(endpoint));
+                }
+                if let Some(turnout) = self.turnouts.at(endpoint) {
+                    neighbors.extend([turnout.points, turnout.normal, turnout.reverse]);
+                }
+                queue.extend(neighbors.into_iter()
+                    .map(|neighbor| neighbor.group)
+                    .filter(|id| !seen.contains(id) && self.groups.contains_key(id)));
+            }
+        }
+        result
+    }
+}
+
+// MARK: - Route finding
+impl Layout {
+    // Shortest sequence of groups leading from one group to another, entering each group
+    // at one end and leaving it at the other
+    pub fn find_route(&self, from: GroupId, to: GroupId) -> Option<Vec<GroupId>> {
+        if !self.groups.contains_key(&from) || !self.groups.contains_key(&to) {
+            return None;
+        }
+        if from == to {
+            return Some(vec![from]);
+        }
+
+        // States are the endpoints through which a group is left
+        let mut previous: HashMap<Endpoint, Option<Endpoint>> = HashMap::new();
+        let mut queue = VecDeque::new();
+        for end in [ConnectionType::Start, ConnectionType::End] {
+            let exit = Endpoint { group: from, end };
+            previous.insert(exit, None);
+            queue.push_back(exit);
+        }
+
+        while let Some(exit) = queue.pop_front() {
+            for entry in self.linked_endpoints(exit) {
+                if !self.groups.contains_key(&entry.group) {
+                    continue;
+                }
+                let next_exit = Endpoint { group: entry.group, end: entry.end.opposite() };
+                if previous.contains_key(&next_exit) {
+                    continue;
+                }
+                previous.insert(next_exit, Some(exit));
+
+                if entry.group == to {
+                    let mut route = vec![to];
+                    let mut current = Some(exit);
+                    while let Some(step) = current {
+                        route.push(step.group);
+                        current = previous[&step];
+                    }
+                    route.reverse();
+                    return Some(route);
+                }
+                queue.push_back(next_exit);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::BlockId;
+
+    // Three separate runs, returning the blocks at their starts and ends
+    fn three_runs(layout: &mut Layout) -> [(BlockId, BlockId); 3] {
+        [0, 4, 8].map(|y| {
+            let run = layout.lay_run((0, y), (2, y)).unwrap();
+            (run[0], run[2])
+        })
+    }
+
+    fn group_of(layout: &Layout, block: BlockId) -> GroupId {
+        layout.group_of_block(block).map(|group| group.id).unwrap()
+    }
+
+    #[test]
+    fn routes_follow_the_active_leg_of_a_turnout() {
+        let mut layout = Layout::default();
+        let [a, b, c] = three_runs(&mut layout);
+        let turnout = layout.add_turnout(a.1, b.0, c.0).unwrap();
+        let [a, b, c] = [a, b, c].map(|(start, _)| group_of(&layout, start));
+
+        assert_eq!(layout.find_route(a, b), Some(vec![a, b]));
+        assert_eq!(layout.find_route(a, c), None);
+
+        layout.toggle_turnout(turnout).unwrap();
+        assert_eq!(layout.find_route(a, b), None);
+        assert_eq!(layout.find_route(a, c), Some(vec![a, c]));
+        // Reachability ignores the position
+        assert_eq!(layout.connected_groups(&[b]).len(), 3);
+    }
+
+    #[test]
+    fn directed_connections_are_only_followed_forwards() {
+        let mut layout = Layout::default();
+        let [a, b, _] = three_runs(&mut layout);
+        layout.connect_groups(a.1, b.0, true).unwrap();
+        let (a, b) = (group_of(&layout, a.0), group_of(&layout, b.0));
+
+        assert_eq!(layout.find_route(a, b), Some(vec![a, b]));
+        assert_eq!(layout.find_route(b, a), None);
+    }
+}
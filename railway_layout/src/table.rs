@@ -0,0 +1,309 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use crate::error::LayoutError;
+use crate::models::Endpoint;
+
+// MARK: - Attached
+// Something hooked onto group endpoints, such as a connection or a turnout
+pub trait Attached {
+    type Id: Copy + Eq + Hash + Ord;
+
+    fn id(&self) -> Self::Id;
+    fn endpoints(&self) -> Vec<Endpoint>;
+    fn endpoints_mut(&mut self) -> Vec<&mut Endpoint>;
+}
+
+// MARK: - EndpointTable
+// Records keyed by id, with an index from every endpoint to the record attached to it.
+// An endpoint holds at most one record; the table refuses inserts that would break that.
+// It is stored as a plain list and the index is rebuilt on load.
+#[derive(Debug, Clone)]
+pub struct EndpointTable<T: Attached> {
+    records: BTreeMap<T::Id, T>,
+    by_endpoint: HashMap<Endpoint, T::Id>,
+    // While an edit is recorded, every record it touched as it was before (`None` if it is new)
+    journal: Option<Journal<T>>,
+}
+
+pub(crate) type Journal<T> = BTreeMap<<T as Attached>::Id, Option<T>>;
+
+impl<T: Attached> Default for EndpointTable<T> {
+    fn default() -> Self {
+        Self {
+            records: BTreeMap::new(),
+            by_endpoint: HashMap::new(),
+            journal: None,
+        }
+    }
+}
+
+impl<T: Attached + Clone> EndpointTable<T> {
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn get(&self, id: T::Id) -> Option<&T> {
+        self.records.get(&id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.records.values()
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = T::Id> + '_ {
+        self.records.keys().copied()
+    }
+
+    // The record attached to the given endpoint
+    pub fn at(&self, endpoint: Endpoint) -> Option<&T> {
+        self.by_endpoint.get(&endpoint).and_then(|id| self.records.get(id))
+    }
+
+    pub fn is_attached(&self, endpoint: Endpoint) -> bool {
+        self.by_endpoint.contains_key(&endpoint)
+    }
+
+    pub fn insert(&mut self, record: T) -> Result<T::Id, LayoutError> {
+        let endpoints = record.endpoints();
+        for (i, endpoint) in endpoints.iter().enumerate() {
+            if endpoints[..i].contains(endpoint) {
+                return Err(LayoutError::DuplicateEndpoint);
+            }
+            if self.is_attached(*endpoint) {
+                return Err(LayoutError::EndpointInUse(*endpoint));
+            }
+        }
+
+        let id = record.id();
+        self.note(id);
+        if let Some(old) = self.records.remove(&id) {
+            self.unindex(&old);
+        }
+        for endpoint in endpoints {
+            self.by_endpoint.insert(endpoint, id);
+        }
+        self.records.insert(id, record);
+        Ok(id)
+    }
+
+    pub fn remove(&mut self, id: T::Id) -> Option<T> {
+        self.note(id);
+        let record = self.records.remove(&id)?;
+        self.unindex(&record);
+        Some(record)
+    }
+
+    pub fn clear(&mut self) {
+        if let Some(journal) = &mut self.journal {
+            for (id, record) in &self.records {
+                journal.entry(*id).or_insert_with(|| Some(record.clone()));
+            }
+        }
+        self.records.clear();
+        self.by_endpoint.clear();
+    }
+
+    // Change a record in place. Endpoints may change too; if they collide with
+    // another record the change is rejected and the record is left as it was.
+    pub fn update<R>(&mut self, id: T::Id, f: impl FnOnce(&mut T) -> R) -> Option<Result<R, LayoutError>> {
+        let original = self.remove(id)?;
+        let mut record = original.clone();
+        let result = f(&mut record);
+        match self.insert(record) {
+            Ok(_) => Some(Ok(result)),
+            Err(e) => {
+                self.insert(original).ok();
+                Some(Err(e))
+            }
+        }
+    }
+
+    // Move every endpoint through `f`. Records with an endpoint that maps to `None`,
+    // or that would collide with an earlier record, are dropped and returned.
+    pub fn remap(&mut self, mut f: impl FnMut(Endpoint) -> Option<Endpoint>) -> Vec<T> {
+        let records = std::mem::take(&mut self.records);
+        self.by_endpoint.clear();
+        // Only records that end up different are noted, so re-inserting must not note them
+        let mut journal = self.journal.take();
+
+        let mut dropped = vec![];
+        for (id, mut record) in records {
+            let original = journal.is_some().then(|| record.clone());
+            let mut complete = true;
+            for endpoint in record.endpoints_mut() {
+                match f(*endpoint) {
+                    Some(new_endpoint) => *endpoint = new_endpoint,
+                    None => complete = false,
+                }
+            }
+            let endpoints = record.endpoints();
+            let collides = endpoints.iter().enumerate().any(|(i, endpoint)|
+                endpoints[..i].contains(endpoint) || self.is_attached(*endpoint)
+            );
+            let kept = complete && !collides;
+            if let (Some(journal), Some(original)) = (&mut journal, original) {
+                if !kept || original.endpoints() != endpoints {
+                    journal.entry(id).or_insert(Some(original));
+                }
+            }
+            if kept {
+                self.insert(record).ok();
+            } else {
+                dropped.push(record);
+            }
+        }
+        self.journal = journal;
+        dropped
+    }
+
+    // Take over the records of another table, e.g. of a layout loaded from disk
+    pub(crate) fn replace(&mut self, other: Self) {
+        self.clear();
+        if let Some(journal) = &mut self.journal {
+            for id in other.records.keys() {
+                journal.entry(*id).or_insert(None);
+            }
+        }
+        self.records = other.records;
+        self.by_endpoint = other.by_endpoint;
+    }
+
+    // Start noting every record that gets changed, see `Layout::begin_change`
+    pub(crate) fn begin_journal(&mut self) {
+        self.journal = Some(Journal::<T>::new());
+    }
+
+    pub(crate) fn end_journal(&mut self) -> Journal<T> {
+        self.journal.take().unwrap_or_default()
+    }
+
+    // Put back the given records, or remove them where `None`. Returns the records as they
+    // were instead, so restoring those undoes this again.
+    pub(crate) fn restore(&mut self, saved: Journal<T>) -> Journal<T> {
+        let mut replaced = Journal::<T>::new();
+        for id in saved.keys() {
+            let current = self.records.remove(id);
+            if let Some(record) = &current {
+                self.unindex(record);
+            }
+            replaced.insert(*id, current);
+        }
+        for record in saved.into_values().flatten() {
+            self.insert_unchecked(record);
+        }
+        replaced
+    }
+
+    fn note(&mut self, id: T::Id) {
+        if let Some(journal) = &mut self.journal {
+            journal.entry(id).or_insert_with(|| self.records.get(&id).cloned());
+        }
+    }
+
+    fn unindex(&mut self, record: &T) {
+        for endpoint in record.endpoints() {
+            if self.by_endpoint.get(&endpoint) == Some(&record.id()) {
+                self.by_endpoint.remove(&endpoint);
+            }
+        }
+    }
+
+    // Unlike `insert` this keeps records that claim an endpoint already taken,
+    // so a damaged file still loads; the index keeps the first claim
+    fn from_records(records: Vec<T>) -> Self {
+        let mut table = Self::default();
+        for record in records {
+            table.insert_unchecked(record);
+        }
+        table
+    }
+
+    fn insert_unchecked(&mut self, record: T) {
+        for endpoint in record.endpoints() {
+            self.by_endpoint.entry(endpoint).or_insert(record.id());
+        }
+        self.records.insert(record.id(), record);
+    }
+}
+
+// MARK: - Serialization
+impl<T: Attached + Serialize> Serialize for EndpointTable<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.records.values())
+    }
+}
+
+impl<'de, T: Attached + Clone + DeserializeOwned> Deserialize<'de> for EndpointTable<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let records = Vec::<T>::deserialize(deserializer)?;
+        Ok(Self::from_records(records))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Connection, ConnectionId, ConnectionType, GroupId};
+
+    fn endpoint(group: u32, end: ConnectionType) -> Endpoint {
+        Endpoint { group: GroupId(group), end }
+    }
+
+    fn connection(id: u32, from: Endpoint, to: Endpoint) -> Connection {
+        Connection { id: ConnectionId(id), from, to, directed: false }
+    }
+
+    #[test]
+    fn an_endpoint_holds_one_record() {
+        let (a, b, c) = (endpoint(1, ConnectionType::End), endpoint(2, ConnectionType::Start), endpoint(3, ConnectionType::Start));
+        let mut table = EndpointTable::default();
+        table.insert(connection(1, a, b)).unwrap();
+
+        assert!(matches!(table.insert(connection(2, a, c)), Err(LayoutError::EndpointInUse(endpoint)) if endpoint == a));
+        assert!(matches!(table.insert(connection(2, c, c)), Err(LayoutError::DuplicateEndpoint)));
+        assert_eq!(table.len(), 1);
+
+        table.remove(ConnectionId(1));
+        table.insert(connection(2, a, c)).unwrap();
+        assert_eq!(table.at(a).map(|conn| conn.id), Some(ConnectionId(2)));
+        assert!(!table.is_attached(b));
+    }
+
+    #[test]
+    fn restoring_a_journal_undoes_what_it_recorded() {
+        let (a, b, c) = (endpoint(1, ConnectionType::End), endpoint(2, ConnectionType::Start), endpoint(3, ConnectionType::Start));
+        let mut table = EndpointTable::default();
+        table.insert(connection(1, a, b)).unwrap();
+
+        table.begin_journal();
+        table.remove(ConnectionId(1));
+        table.insert(connection(2, b, c)).unwrap();
+        let journal = table.end_journal();
+
+        let redo = table.restore(journal);
+        assert_eq!(table.ids().collect::<Vec<_>>(), [ConnectionId(1)]);
+        assert_eq!(table.at(a).map(|conn| conn.id), Some(ConnectionId(1)));
+        assert!(!table.is_attached(c));
+
+        table.restore(redo);
+        assert_eq!(table.ids().collect::<Vec<_>>(), [ConnectionId(2)]);
+        assert!(!table.is_attached(a));
+    }
+
+    #[test]
+    fn loading_keeps_the_first_claim_on_an_endpoint() {
+        let (a, b, c) = (endpoint(1, ConnectionType::End), endpoint(2, ConnectionType::Start), endpoint(3, ConnectionType::Start));
+        let json = serde_json::to_string(&[connection(1, a, b), connection(2, a, c)]).unwrap();
+        let table: EndpointTable<Connection> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.at(a).map(|conn| conn.id), Some(ConnectionId(1)));
+        assert_eq!(table.at(c).map(|conn| conn.id), Some(ConnectionId(2)));
+    }
+}
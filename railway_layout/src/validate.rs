@@ -0,0 +1,312 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use crate::layout::{connected_components, Layout};
+use crate::models::{Block, BlockId, ConnectionId, ConnectionType, Direction, Endpoint, GroupId, TurnoutId};
+use crate::table::Attached;
+
+// Consistency checks for layouts that were loaded from disk or edited by hand.
+// The editor never produces these states itself, so finding one means the file is damaged.
+
+// MARK: - Severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    // The layout is inconsistent and editing it may lose data
+    Error,
+    // Stale or redundant data that can be recomputed
+    Warning,
+}
+
+// MARK: - Problem
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    // A group is stored under a different id than its own
+    GroupIdMismatch { key: GroupId, group: GroupId },
+    EmptyGroup(GroupId),
+    // The same block id is used more than once
+    DuplicateBlockId(BlockId),
+    // Several blocks occupy the same cell
+    OverlappingBlocks { grid_pos: (i32, i32), blocks: Vec<BlockId> },
+    // `start_block_id` or `end_block_id` names a block that is not in the group
+    MissingEndpointBlock { group: GroupId, end: ConnectionType, block: BlockId },
+    // `start_block_id` or `end_block_id` is not set on a group with blocks
+    UnsetEndpoint { group: GroupId, end: ConnectionType },
+    // The blocks of a group form more than one run of track
+    NonContiguousGroup { group: GroupId, runs: usize },
+    // The stored direction does not match the blocks
+    DirectionMismatch { group: GroupId, stored: Option<Direction>, expected: Option<Direction> },
+    DanglingConnection { connection: ConnectionId, group: GroupId },
+    DanglingTurnout { turnout: TurnoutId, group: GroupId },
+    // An endpoint claimed by more than one connection or turnout
+    SharedEndpoint(Endpoint),
+}
+
+impl Problem {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Problem::EmptyGroup(_) | Problem::DirectionMismatch { .. } => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::GroupIdMismatch { key, group } => write!(f, "Group {} is stored under id {}", group, key),
+            Problem::EmptyGroup(group) => write!(f, "Group {} has no blocks", group),
+            Problem::DuplicateBlockId(block) => write!(f, "Block id {} is used more than once", block),
+            Problem::OverlappingBlocks { grid_pos, blocks } => {
+                write!(f, "{} blocks overlap at {:?}", blocks.len(), grid_pos)
+            }
+            Problem::MissingEndpointBlock { group, end, block } => {
+                write!(f, "{:?} block {} of group {} is not in the group", end, block, group)
+            }
+            Problem::UnsetEndpoint { group, end } => write!(f, "Group {} has no {:?} block", group, end),
+            Problem::NonContiguousGroup { group, runs } => {
+                write!(f, "Group {} is split into {} separate runs", group, runs)
+            }
+            Problem::DirectionMismatch { group, stored, expected } => {
+                write!(f, "Group {} is stored as {} but its blocks are {}", group, describe(*stored), describe(*expected))
+            }
+            Problem::DanglingConnection { connection, group } => {
+                write!(f, "Connection {} points at missing group {}", connection, group)
+            }
+            Problem::DanglingTurnout { turnout, group } => {
+                write!(f, "Turnout {} points at missing group {}", turnout, group)
+            }
+            Problem::SharedEndpoint(endpoint) => write!(f, "The {} is claimed more than once", endpoint),
+        }
+    }
+}
+
+fn describe(direction: Option<Direction>) -> String {
+    direction.map_or("not straight".to_string(), |direction| format!("{:?}", direction))
+}
+
+// MARK: - Diagnostic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub problem: Problem,
+    // Blocks involved, for pointing the user at the problem
+    pub blocks: Vec<BlockId>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.severity, self.problem)
+    }
+}
+
+// MARK: - Validation
+impl Layout {
+    // Check the layout for inconsistencies. Errors come before warnings.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+        let mut report = |problem: Problem, blocks: Vec<BlockId>| {
+            diagnostics.push(Diagnostic { severity: problem.severity(), problem, blocks });
+        };
+
+        // Sorted so the list comes out the same every time
+        let mut group_ids: Vec<GroupId> = self.groups.keys().copied().collect();
+        group_ids.sort();
+
+        let mut cells: BTreeMap<(i32, i32), Vec<BlockId>> = BTreeMap::new();
+        let mut block_uses: BTreeMap<BlockId, usize> = BTreeMap::new();
+
+        for key in &group_ids {
+            let group = &self.groups[key];
+            let all_blocks: Vec<BlockId> = group.blocks.iter().map(|block| block.id).collect();
+
+            if group.id != *key {
+                report(Problem::GroupIdMismatch { key: *key, group: group.id }, all_blocks.clone());
+            }
+            if group.blocks.is_empty() {
+                report(Problem::EmptyGroup(*key), vec![]);
+                continue;
+            }
+
+            for block in &group.blocks {
+                cells.entry(block.grid_pos).or_default().push(block.id);
+                *block_uses.entry(block.id).or_default() += 1;
+            }
+
+            for (end, block_id) in [
+                (ConnectionType::Start, group.start_block_id),
+                (ConnectionType::End, group.end_block_id),
+            ] {
+                match block_id {
+                    Some(block) if !all_blocks.contains(&block) => {
+                        report(Problem::MissingEndpointBlock { group: *key, end, block }, all_blocks.clone());
+                    }
+                    Some(_) => {}
+                    None => report(Problem::UnsetEndpoint { group: *key, end }, all_blocks.clone()),
+                }
+            }
+
+            let runs = connected_components(&group.blocks).len();
+            if runs > 1 {
+                report(Problem::NonContiguousGroup { group: *key, runs }, all_blocks.clone());
+            }
+
+            // The direction follows from the piece kinds, but only holds if the blocks line up
+            let mut recomputed = group.clone();
+            recomputed.update_direction();
+            let expected = recomputed.direction.filter(|direction| on_one_line(*direction, &group.blocks));
+            if expected != group.direction {
+                report(
+                    Problem::DirectionMismatch { group: *key, stored: group.direction, expected },
+                    all_blocks,
+                );
+            }
+        }
+
+        for (grid_pos, blocks) in cells {
+            if blocks.len() > 1 {
+                report(Problem::OverlappingBlocks { grid_pos, blocks: blocks.clone() }, blocks);
+            }
+        }
+        for (block, uses) in block_uses {
+            if uses > 1 {
+                report(Problem::DuplicateBlockId(block), vec![block]);
+            }
+        }
+
+        let endpoint_blocks = |endpoints: &[Endpoint]| -> Vec<BlockId> {
+            endpoints.iter().filter_map(|endpoint| self.endpoint_block(*endpoint)).map(|block| block.id).collect()
+        };
+        let mut claims: HashMap<Endpoint, usize> = HashMap::new();
+
+        for connection in self.connections.iter() {
+            let endpoints = connection.endpoints();
+            for endpoint in &endpoints {
+                *claims.entry(*endpoint).or_default() += 1;
+                if !self.groups.contains_key(&endpoint.group) {
+                    report(
+                        Problem::DanglingConnection { connection: connection.id, group: endpoint.group },
+                        endpoint_blocks(&endpoints),
+                    );
+                }
+            }
+        }
+        for turnout in self.turnouts.iter() {
+            let endpoints = turnout.endpoints();
+            for endpoint in &endpoints {
+                *claims.entry(*endpoint).or_default() += 1;
+                if !self.groups.contains_key(&endpoint.group) {
+                    report(
+                        Problem::DanglingTurnout { turnout: turnout.id, group: endpoint.group },
+                        endpoint_blocks(&endpoints),
+                    );
+                }
+            }
+        }
+
+        let mut shared: Vec<Endpoint> = claims.into_iter()
+            .filter(|(_, count)| *count > 1)
+            .map(|(endpoint, _)| endpoint)
+            .collect();
+        shared.sort_by_key(|endpoint| (endpoint.group, endpoint.end == ConnectionType::End));
+        for endpoint in shared {
+            report(Problem::SharedEndpoint(endpoint), endpoint_blocks(&[endpoint]));
+        }
+
+        diagnostics.sort_by_key(|diagnostic| diagnostic.severity);
+        diagnostics
+    }
+}
+
+// Whether all blocks lie on one line along the given axis
+fn on_one_line(direction: Direction, blocks: &[Block]) -> bool {
+    let line = |(x, y): (i32, i32)| match direction {
+        Direction::Horizontal => y,
+        Direction::Vertical => x,
+        Direction::DiagonalUp => x + y,
+        Direction::DiagonalDown => x - y,
+    };
+    let Some(first) = blocks.first() else {
+        return true;
+    };
+    blocks.iter().all(|block| line(block.grid_pos) == line(first.grid_pos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::BlockKind;
+
+    // A straight run and the id of the group it forms
+    fn run(layout: &mut Layout, start: (i32, i32), end: (i32, i32)) -> (GroupId, Vec<BlockId>) {
+        let blocks = layout.lay_run(start, end).unwrap();
+        (layout.group_of_block(blocks[0]).unwrap().id, blocks)
+    }
+
+    fn diagnostic(problem: Problem, blocks: Vec<BlockId>) -> Diagnostic {
+        Diagnostic { severity: problem.severity(), problem, blocks }
+    }
+
+    #[test]
+    fn blocks_of_different_groups_on_one_cell_overlap() {
+        let mut layout = Layout::default();
+        let kind = BlockKind::Straight(Direction::Horizontal);
+        let a = layout.place_block((0, 0), kind).unwrap();
+        let b = layout.place_block((5, 5), kind).unwrap();
+        let group = layout.group_of_block(b).unwrap().id;
+        layout.groups.get_mut(&group).unwrap().blocks[0].grid_pos = (0, 0);
+
+        let problem = Problem::OverlappingBlocks { grid_pos: (0, 0), blocks: vec![a, b] };
+        assert_eq!(problem.severity(), Severity::Error);
+        assert_eq!(layout.validate(), [diagnostic(problem, vec![a, b])]);
+    }
+
+    #[test]
+    fn connection_to_a_removed_group_dangles() {
+        let mut layout = Layout::default();
+        let (_, a) = run(&mut layout, (0, 0), (2, 0));
+        let (gone, b) = run(&mut layout, (0, 2), (2, 2));
+        let connection = layout.connect_groups(a[2], b[0], false).unwrap();
+        layout.groups.remove(&gone);
+
+        let problem = Problem::DanglingConnection { connection, group: gone };
+        assert_eq!(problem.severity(), Severity::Error);
+        assert_eq!(layout.validate(), [diagnostic(problem, vec![a[2]])]);
+    }
+
+    #[test]
+    fn start_block_outside_the_group_is_missing() {
+        let mut layout = Layout::default();
+        let (group, blocks) = run(&mut layout, (0, 0), (2, 0));
+        let (_, other) = run(&mut layout, (0, 2), (2, 2));
+        layout.groups.get_mut(&group).unwrap().start_block_id = Some(other[0]);
+
+        let problem = Problem::MissingEndpointBlock { group, end: ConnectionType::Start, block: other[0] };
+        assert_eq!(problem.severity(), Severity::Error);
+        assert_eq!(layout.validate(), [diagnostic(problem, blocks)]);
+    }
+
+    #[test]
+    fn group_with_a_gap_is_not_contiguous() {
+        let mut layout = Layout::default();
+        let (group, blocks) = run(&mut layout, (0, 0), (3, 0));
+        layout.groups.get_mut(&group).unwrap().blocks.retain(|block| block.id != blocks[1]);
+
+        let problem = Problem::NonContiguousGroup { group, runs: 2 };
+        assert_eq!(problem.severity(), Severity::Error);
+        assert_eq!(layout.validate(), [diagnostic(problem, vec![blocks[0], blocks[2], blocks[3]])]);
+    }
+
+    #[test]
+    fn stored_direction_must_match_the_blocks() {
+        let mut layout = Layout::default();
+        let (group, blocks) = run(&mut layout, (0, 0), (2, 0));
+        layout.groups.get_mut(&group).unwrap().direction = Some(Direction::Vertical);
+
+        let problem = Problem::DirectionMismatch {
+            group,
+            stored: Some(Direction::Vertical),
+            expected: Some(Direction::Horizontal),
+        };
+        assert_eq!(problem.severity(), Severity::Warning);
+        assert_eq!(layout.validate(), [diagnostic(problem, blocks)]);
+    }
+}
@@ -0,0 +1,111 @@
+use railway_layout::format::CURRENT_FORMAT_VERSION;
+use railway_layout::{
+    BlockId, BlockKind, ConnectionType, Direction, Endpoint, GroupId, Layout, LayoutError, Port, TurnoutPosition,
+};
+
+// One fixture per historical format version; each must load and come out valid
+
+fn endpoint(group: u32, end: ConnectionType) -> Endpoint {
+    Endpoint { group: GroupId(group), end }
+}
+
+fn kind_of(layout: &Layout, block: u32) -> BlockKind {
+    layout.groups.values()
+        .flat_map(|group| group.blocks.iter())
+        .find(|b| b.id == BlockId(block))
+        .map(|b| b.kind)
+        .unwrap()
+}
+
+#[test]
+fn loads_v0_baseline() {
+    let layout = Layout::from_json(include_str!("fixtures/v0_baseline.json")).unwrap();
+
+    assert_eq!(layout.block_count(), 5);
+    assert_eq!(kind_of(&layout, 1), BlockKind::Straight(Direction::Horizontal));
+    assert_eq!(kind_of(&layout, 4), BlockKind::Straight(Direction::Vertical));
+
+    // The `Unknown` connection is dropped, the other one moves to the layout table
+    assert_eq!(layout.connections.len(), 1);
+    let connection = layout.connections.at(endpoint(1, ConnectionType::End)).unwrap();
+    assert_eq!(connection.to, endpoint(2, ConnectionType::Start));
+    assert!(connection.directed);
+
+    assert_eq!(layout.next_connection_id, 2);
+    assert!(layout.validate().is_empty());
+}
+
+#[test]
+fn loads_v0_with_track_kinds_and_turnout_map() {
+    let layout = Layout::from_json(include_str!("fixtures/v0_track_kinds.json")).unwrap();
+
+    assert_eq!(kind_of(&layout, 3), BlockKind::Curve(Port::South));
+    assert_eq!(kind_of(&layout, 5), BlockKind::BufferStop(Port::West));
+
+    assert_eq!(layout.turnouts.len(), 1);
+    let turnout = layout.turnouts.at(endpoint(1, ConnectionType::End)).unwrap();
+    assert_eq!(turnout.position, TurnoutPosition::Reverse);
+    assert_eq!(turnout.reverse, endpoint(4, ConnectionType::Start));
+
+    assert_eq!(layout.connections.len(), 1);
+    assert!(layout.connections.is_attached(endpoint(1, ConnectionType::Start)));
+    assert!(layout.validate().is_empty());
+}
+
+#[test]
+fn loads_v1() {
+    let layout = Layout::from_json(include_str!("fixtures/v1.json")).unwrap();
+
+    assert_eq!(layout.groups.len(), 3);
+    let connection = layout.connections.at(endpoint(2, ConnectionType::Start)).unwrap();
+    assert!(!connection.directed);
+    assert!(layout.groups.values().all(|group| group.name.is_none() && !group.reversed));
+    assert!(layout.validate().is_empty());
+}
+
+#[test]
+fn loads_v2_with_named_and_reversed_groups() {
+    let layout = Layout::from_json(include_str!("fixtures/v2.json")).unwrap();
+
+    let main_line = &layout.groups[&GroupId(1)];
+    assert_eq!(main_line.name.as_deref(), Some("Main line"));
+    assert!(main_line.reversed);
+    assert_eq!(main_line.start_block_id, Some(BlockId(3)));
+    assert_eq!(main_line.end_block_id, Some(BlockId(1)));
+    assert!(!layout.groups[&GroupId(2)].reversed);
+
+    let connection = layout.connections.at(endpoint(1, ConnectionType::Start)).unwrap();
+    assert_eq!(connection.to, endpoint(2, ConnectionType::Start));
+    assert!(layout.validate().is_empty());
+
+    let reloaded = Layout::from_json(&layout.to_json().unwrap()).unwrap();
+    assert_eq!(reloaded.groups[&GroupId(1)], *main_line);
+}
+
+#[test]
+fn saves_current_version() {
+    let mut layout = Layout::default();
+    layout.place_block_auto((0, 0)).unwrap();
+
+    let json: serde_json::Value = serde_json::from_str(&layout.to_json().unwrap()).unwrap();
+    assert_eq!(json["format_version"], CURRENT_FORMAT_VERSION);
+}
+
+#[test]
+fn round_trips_through_current_version() {
+    let layout = Layout::from_json(include_str!("fixtures/v0_track_kinds.json")).unwrap();
+    let reloaded = Layout::from_json(&layout.to_json().unwrap()).unwrap();
+
+    assert_eq!(reloaded.block_count(), layout.block_count());
+    assert_eq!(reloaded.connections.len(), layout.connections.len());
+    assert_eq!(reloaded.turnouts.len(), layout.turnouts.len());
+}
+
+#[test]
+fn rejects_newer_version() {
+    let contents = format!(r#"{{ "format_version": {}, "groups": {{}} }}"#, CURRENT_FORMAT_VERSION + 1);
+
+    let error = Layout::from_json(&contents).unwrap_err();
+    assert!(matches!(error, LayoutError::NewerFormat { .. }));
+    assert!(error.to_string().contains("written by a newer version"));
+}
@@ -1,17 +1,65 @@
 use eframe::egui;
-use crate::editor::{RailwayEditor, AppMode};
-use crate::models::{Block, Group};
-use crate::rendering::{draw_grid, draw_blocks, draw_connections};
+use crate::editor::{RailwayEditor, TRACK_PIECES};
+use crate::rendering::{draw_grid, draw_blocks, draw_connections, draw_endpoint_markers, draw_suggestions, draw_turnouts, RenderStyle};
+use crate::tools::{ConnectMode, Tool};
 use crate::utils::*;
+use railway_layout::{Attachment, Command, ConnectionType, Endpoint, Severity, Transform};
 
 // MARK: - Update
 impl eframe::App for RailwayEditor {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Handle keyboard input for deletion
-        if ctx.input(|i| i.key_pressed(egui::Key::Delete)) {
-            self.remove_selected_blocks();
+        if !ctx.wants_keyboard_input() && ctx.input(|i| i.key_pressed(egui::Key::Delete)) {
+            self.delete_selection();
         }
 
+        // Undo with Ctrl+Z, redo with Ctrl+Shift+Z
+        if !ctx.wants_keyboard_input() {
+            let (undo, redo) = ctx.input(|i| {
+                let pressed = i.modifiers.command && i.key_pressed(egui::Key::Z);
+                (pressed && !i.modifiers.shift, pressed && i.modifiers.shift)
+            });
+            if undo {
+                self.undo();
+            }
+            if redo {
+                self.redo();
+            }
+        }
+
+        // Copy, cut and paste through the system clipboard; Ctrl+D duplicates
+        if !ctx.wants_keyboard_input() {
+            let events = ctx.input(|i| i.events.clone());
+            for event in events {
+                match event {
+                    egui::Event::Copy => {
+                        self.copy_selection(ctx);
+                    }
+                    egui::Event::Cut => self.cut_selection(ctx),
+                    egui::Event::Paste(text) => {
+                        let origin = ctx.pointer_hover_pos().map_or((0, 0), |pos| self.camera.screen_to_grid(pos));
+                        self.paste(&text, origin);
+                    }
+                    _ => {}
+                }
+            }
+            if ctx.input(|i| i.modifiers.command && i.key_pressed(egui::Key::D)) {
+                self.duplicate_selection();
+            }
+        }
+
+        // Rotate the track piece to be placed
+        if !ctx.wants_keyboard_input() && ctx.input(|i| i.key_pressed(egui::Key::R)) {
+            self.rotate_placing_kind();
+        }
+
+        // Tool shortcuts
+        self.handle_tool_hotkeys(ctx);
+        self.handle_view_hotkeys(ctx);
+
+        // ===== Status bar ===== 
+        self.draw_status_bar(ctx);
+
         egui::CentralPanel::default().show(ctx, |ui| {
             let response = ui.allocate_rect(
                 ui.available_rect_before_wrap(),
@@ -19,111 +67,100 @@ impl eframe::App for RailwayEditor {
             );
 
             // ===== Draw grid background ===== 
-            let painter = ui.painter();
-            let rect = response.rect;
-            draw_grid(painter, rect);
-
-            // ===== Handle block placement ===== 
-            if response.clicked() && !response.dragged() {
-                if let Some(pos) = response.interact_pointer_pos() {
-                    let grid_pos = snap_to_grid(pos);
-                    
-                    // Check if position is occupied in any group
-                    let position_occupied = self.groups.values().any(|group| 
-                        group.blocks.iter().any(|block| block.grid_pos == grid_pos)
-                    );
-
-                    if !position_occupied {
-                        let new_block = Block {
-                            id: self.next_block_id,
-                            grid_pos,
-                        };
+            self.camera.viewport = response.rect;
+            draw_grid(ui.painter(), &self.camera);
 
-                        let neighboring_groups = self.find_neighboring_groups(grid_pos);
-                        
-                        if neighboring_groups.is_empty() {
-                            // Create new group with the new block
-                            self.groups.insert(self.next_group_id, Group {
-                                id: self.next_group_id,
-                                blocks: vec![new_block],
-                                connections: vec![],
-                                direction: None,
-                                start_block_id: None,
-                                end_block_id: None,
-                            });
-                            self.next_block_id += 1;
-                            self.next_group_id += 1;
-                        } else {
-                            // Add to existing groups
-                            for group_id in &neighboring_groups {
-                                if self.can_add_to_group(*group_id, grid_pos) {
-                                    if let Some(group) = self.groups.get_mut(group_id) {
-                                        group.blocks.push(new_block.clone());
-                                        group.update_start_end_blocks();
-                                        self.update_group_direction(*group_id);
-                                        break;
-                                    }
-                                }
-                            }
-                            self.next_block_id += 1;
-
-                            // Merge groups if necessary
-                            if neighboring_groups.len() > 1 {
-                                let first_group = neighboring_groups[0];
-                                for &group_id in &neighboring_groups[1..] {
-                                    self.merge_groups(group_id, first_group);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
             // ===== Draw blocks and connections ===== 
             draw_blocks(self, ui);
             draw_connections(self, ui);
-
-            // ===== Handle block selection ===== 
-            for group in self.groups.values() {
-                for block in &group.blocks {
-                    let center = grid_to_screen(block.grid_pos);
-                    let rect = egui::Rect::from_center_size(center, egui::vec2(BLOCK_SIZE, BLOCK_SIZE));
-                    
-                    let block_response = ui.interact(rect, egui::Id::new(block.id), egui::Sense::click());
-                    
-                    if block_response.clicked() {
-                        let start_id = if self.app_mode == AppMode::SetConnections { group.start_block_id } else { None };
-                        let end_id = if self.app_mode == AppMode::SetConnections { group.end_block_id } else { None };
-
-                        if ui.input(|i| i.modifiers.shift) { // shift + click
-                            if self.selected_blocks.contains(&block.id) {
-                                self.selected_blocks.shift_remove(&block.id);
-                            } else {
-                                if group.check_selected_blocks(block.id, start_id, end_id) {
-                                    self.selected_blocks.insert(block.id);
-                                } else {
-                                    self.message = "Cannot select the block".to_string();
-                                    self.show_message_box = true;
-                                }
-                            }
-                        } else { // click
-                            self.selected_blocks.clear();
-                            if group.check_selected_blocks(block.id, start_id, end_id) {
-                                self.selected_blocks.insert(block.id);
-                            } else {
-                                self.message = "Cannot select the block".to_string();
-                                self.show_message_box = true;
-                            }
-                        }
-                    } 
-                }
+            draw_turnouts(self, ui);
+            draw_suggestions(self, ui);
+            if self.tool == Tool::Connect {
+                draw_endpoint_markers(self, ui);
             }
+
+            // ===== Active tool ===== 
+            self.handle_tool_input(ui, &response);
+            self.handle_context_menu(&response);
+
             // ===== Toolbar window ===== 
+            self.draw_tool_palette(ctx);
             self.draw_toolbar(ctx);
+            self.draw_connection_window(ctx);
+            self.draw_suggestions_panel(ctx);
+            self.draw_rename_window(ctx);
+            self.draw_group_properties(ctx);
+            self.draw_problems_panel(ctx);
+            self.draw_history_panel(ctx);
+            self.draw_minimap(ctx);
+        });
+    }
+}
 
-            // ===== Set Connection ===== 
-            if self.app_mode == AppMode::SetConnections {
-                self.connect_groups();
-            }
+// MARK: - Tool palette
+impl RailwayEditor {
+    fn draw_tool_palette(&mut self, ctx: &egui::Context) {
+        egui::Window::new(TOOL_PALETTE_TITLE)
+            .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-8.0, 8.0))
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                for tool in Tool::ALL {
+                    let text = format!("{} ({})", tool.label(), tool.hotkey().name());
+                    if ui.selectable_label(self.tool == tool, text).on_hover_text(tool.hint()).clicked() {
+                        self.set_tool(tool);
+                    }
+                }
+
+                ui.separator();
+                if ui.button("Zoom to Fit (Home)").clicked() {
+                    self.zoom_to_fit();
+                }
+                if ui.button("Zoom to Selection (F)").clicked() {
+                    self.zoom_to_selection();
+                }
+                ui.checkbox(&mut self.show_minimap, "Minimap");
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    for style in RenderStyle::ALL {
+                        ui.selectable_value(&mut self.render_style, style, style.label());
+                    }
+                })
+                .response
+                .on_hover_text("Draw blocks as cells or as schematic track (T)");
+            });
+    }
+}
+
+// MARK: - Status bar
+impl RailwayEditor {
+    fn draw_status_bar(&mut self, ctx: &egui::Context) {
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.strong(self.tool.label());
+                if self.tool == Tool::Place {
+                    ui.label(self.placing_kind.map_or("Auto", |kind| kind.label()));
+                }
+                if self.tool == Tool::Connect && self.connect_mode == ConnectMode::Turnouts {
+                    ui.label(match self.selected_blocks.len() {
+                        0 => "Turnout: pick the points",
+                        1 => "Turnout: pick the normal leg",
+                        2 => "Turnout: pick the reverse leg",
+                        3 => "Turnout: click the reverse leg again to add it",
+                        _ => "Turnout: pick only three ends",
+                    });
+                }
+                ui.separator();
+                ui.label(self.tool.hint());
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    ui.label(format!("{:.0}%", self.camera.zoom * 100.0));
+                    if let Some(pos) = ctx.pointer_hover_pos() {
+                        ui.separator();
+                        ui.label(format!("{:?}", self.camera.screen_to_grid(pos)));
+                    }
+                });
+            });
         });
     }
 }
@@ -132,34 +169,62 @@ impl eframe::App for RailwayEditor {
 impl RailwayEditor {
     fn draw_toolbar(&mut self, ctx: &egui::Context) {
         egui::Window::new("Controls").show(ctx, |ui| {
-            ui.horizontal(|ui| {
-
-                // ===== Remove Selected Block Button ===== 
-                if ui.button("Remove Selected Block").clicked() {
-                    self.remove_selected_blocks();
-                }
+            // ===== Remove Selected Block Button ===== 
+            if ui.button("Remove Selected Block").clicked() {
+                self.remove_selected_blocks();
+            }
 
-                // ===== Hide/Show Connections Button ===== 
-                let connection_text = if self.show_connection_panel { 
-                    self.app_mode = AppMode::SetConnections;
-                    "Hide Connections".to_string()
-                } else { 
-                    self.app_mode = AppMode::Normal;
-                    "Show Connections".to_string()
-                };
+            // ===== Track Piece Picker ===== 
+            ui.horizontal(|ui| {
+                ui.label("Track Piece:");
+                egui::ComboBox::from_id_source("track_piece")
+                    .selected_text(self.placing_kind.map_or("Auto", |kind| kind.label()))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.placing_kind, None, "Auto");
+                        for kind in TRACK_PIECES {
+                            ui.selectable_value(&mut self.placing_kind, Some(kind), kind.label());
+                        }
+                    });
 
-                if ui.button(connection_text).clicked() {
-                    self.show_connection_panel = !self.show_connection_panel;
+                if ui.add_enabled(self.placing_kind.is_some(), egui::Button::new("Rotate (R)")).clicked() {
+                    self.rotate_placing_kind();
                 }
             });
-
             
             ui.separator();
 
             // ===== Labels ===== 
-            ui.label(format!("Total Blocks: {}", self.groups.values().map(|g| g.blocks.len()).sum::<usize>()));
+            ui.label(format!("Total Blocks: {}", self.layout.block_count()));
             ui.label(format!("Selected Blocks: {}", self.selected_blocks.len()));
-            ui.label(format!("Total Groups: {}", self.groups.len()));
+            ui.horizontal(|ui| {
+                let has_selection = !self.selected_blocks.is_empty();
+                if ui.add_enabled(has_selection, egui::Button::new("Select Connected")).clicked() {
+                    self.select_connected();
+                }
+                if ui.add_enabled(has_selection, egui::Button::new("Copy")).clicked() {
+                    self.copy_selection(ctx);
+                }
+                if ui.add_enabled(has_selection, egui::Button::new("Cut")).clicked() {
+                    self.cut_selection(ctx);
+                }
+                if ui.add_enabled(has_selection, egui::Button::new("Duplicate")).clicked() {
+                    self.duplicate_selection();
+                }
+            });
+            ui.horizontal(|ui| {
+                let has_selection = !self.selected_blocks.is_empty();
+                for (transform, text) in [
+                    (Transform::RotateCcw, "Rotate ⟲"),
+                    (Transform::RotateCw, "Rotate ⟳"),
+                    (Transform::MirrorHorizontal, "Mirror ↔"),
+                    (Transform::MirrorVertical, "Mirror ↕"),
+                ] {
+                    if ui.add_enabled(has_selection, egui::Button::new(text)).on_hover_text(transform.label()).clicked() {
+                        self.transform_selected_blocks(transform);
+                    }
+                }
+            });
+            ui.label(format!("Total Groups: {}", self.layout.groups.len()));
             
             // ===== Save Layout Button ===== 
             if ui.button("Save Layout").clicked() {
@@ -171,7 +236,34 @@ impl RailwayEditor {
                 self.load_layout();
             }
 
-            if self.show_connection_panel {
+            // ===== Compact IDs Button ===== 
+            if ui.button("Compact IDs").clicked() {
+                self.compact_ids();
+            }
+
+            // ===== Undo / Redo Buttons ===== 
+            ui.horizontal(|ui| {
+                if ui.add_enabled(self.history.can_undo(), egui::Button::new("Undo")).clicked() {
+                    self.undo();
+                }
+                if ui.add_enabled(self.history.can_redo(), egui::Button::new("Redo")).clicked() {
+                    self.redo();
+                }
+                let history_text = if self.show_history_panel { "Hide History" } else { "Show History" };
+                if ui.button(history_text).clicked() {
+                    self.show_history_panel = !self.show_history_panel;
+                }
+            });
+
+            // ===== Check Layout Button ===== 
+            if ui.button("Check Layout").clicked() {
+                self.check_layout();
+                if self.problems.is_empty() {
+                    self.show_message("No problems found");
+                }
+            }
+
+            if self.tool == Tool::Connect {
                 self.draw_connection_panel(ui);
             }
 
@@ -187,26 +279,283 @@ impl RailwayEditor {
     fn draw_connection_panel(&mut self, ui: &mut egui::Ui) {
 
         // ===== Set Connections Button ===== 
-        ui.label(format!("Set Connections:"));
+        ui.label("Set Connections:");
         ui.horizontal(|ui| {
-            if ui.button("Connect Start").clicked() {
-                self.message = "Start Connections in progress (This button does not work yet)!".to_string();
-                self.show_message_box = true;
+            if ui.button("Connect Start").on_hover_text("Suggest connections between touching group ends").clicked() {
+                self.suggest_connections();
             }
 
-            // ===== Remove Connection Button ===== 
-            if ui.button("Remove Connection").clicked() {
-                for group in self.groups.values_mut() {
-                    group.connections.clear();
-                }
+            // ===== Add Turnout Button ===== 
+            let turnout_text = if self.connect_mode == ConnectMode::Turnouts { "Cancel Turnout" } else { "Add Turnout" };
+            if ui.button(turnout_text).clicked() {
+                self.selected_blocks.clear();
+                self.connect_mode = match self.connect_mode {
+                    ConnectMode::Turnouts => ConnectMode::Connections,
+                    ConnectMode::Connections => ConnectMode::Turnouts,
+                };
+            }
 
-                self.message = "Removed Connections Successfully!".to_string();
-                self.show_message_box = true;
+            // ===== Remove All Connections Button ===== 
+            if ui.button("Remove All Connections").clicked() {
+                self.clear_connections();
+                self.show_message("Removed Connections Successfully!");
             }
+
+            ui.checkbox(&mut self.connect_bidirectional, "Bidirectional");
         });
     }
 }
 
+// MARK: - Connection window
+impl RailwayEditor {
+    // Edit the selected connection: which end of each group it attaches to, its direction
+    fn draw_connection_window(&mut self, ctx: &egui::Context) {
+        let Some(connection) = self.selected_connection.and_then(|id| self.layout.connections.get(id)).cloned() else {
+            return;
+        };
+        let mut open = true;
+        let mut edit = None;
+        let mut reverse = false;
+        let mut delete = false;
+        egui::Window::new(CONNECTION_PANEL_TITLE)
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!("Connection {}", connection.id));
+                egui::Grid::new("connection_ends").show(ui, |ui| {
+                    for (label, endpoint, is_from) in [("From", connection.from, true), ("To", connection.to, false)] {
+                        ui.label(format!("{} group {}", label, endpoint.group));
+                        for (end, text) in [(ConnectionType::Start, "Start"), (ConnectionType::End, "End")] {
+                            if ui.selectable_label(endpoint.end == end, text).clicked() && endpoint.end != end {
+                                let moved = Endpoint { end, ..endpoint };
+                                edit = Some(if is_from {
+                                    (moved, connection.to, connection.directed)
+                                } else {
+                                    (connection.from, moved, connection.directed)
+                                });
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+
+                let mut bidirectional = !connection.directed;
+                if ui.checkbox(&mut bidirectional, "Bidirectional").changed() {
+                    edit = Some((connection.from, connection.to, !bidirectional));
+                }
+                ui.horizontal(|ui| {
+                    reverse = ui.button("Reverse").on_hover_text("Swap the ends of the connection").clicked();
+                    delete = ui.button("Delete").clicked();
+                });
+            });
+
+        if let Some((from, to, directed)) = edit {
+            self.edit_connection(connection.id, from, to, directed);
+        }
+        if reverse {
+            self.reverse_connection(connection.id);
+        }
+        if delete {
+            self.delete_selection();
+        }
+        if !open {
+            self.selected_connection = None;
+        }
+    }
+}
+
+// MARK: - Suggestions panel
+impl RailwayEditor {
+    // Review the connections proposed by Connect Start; closing the window rejects them
+    fn draw_suggestions_panel(&mut self, ctx: &egui::Context) {
+        if self.suggestions.is_empty() {
+            return;
+        }
+        let mut open = true;
+        let mut accept = None;
+        egui::Window::new(SUGGESTIONS_PANEL_TITLE)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical().max_height(SUGGESTIONS_LIST_HEIGHT).show(ui, |ui| {
+                    for suggestion in &mut self.suggestions {
+                        ui.checkbox(&mut suggestion.picked, format!("{} to {}", suggestion.from, suggestion.to));
+                    }
+                });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("Accept All").clicked() {
+                        accept = Some(false);
+                    }
+                    let any_picked = self.suggestions.iter().any(|suggestion| suggestion.picked);
+                    if ui.add_enabled(any_picked, egui::Button::new("Accept Selected")).clicked() {
+                        accept = Some(true);
+                    }
+                    if ui.button("Reject").clicked() {
+                        self.reject_suggestions();
+                    }
+                });
+            });
+
+        if let Some(only_picked) = accept {
+            self.accept_suggestions(only_picked);
+        }
+        if !open {
+            self.reject_suggestions();
+        }
+    }
+}
+
+// MARK: - Groups
+impl RailwayEditor {
+    fn draw_rename_window(&mut self, ctx: &egui::Context) {
+        let Some((group_id, mut name)) = self.renaming.take() else {
+            return;
+        };
+        let mut open = true;
+        let mut done = false;
+        egui::Window::new(RENAME_WINDOW_TITLE)
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!("Name for group {} (empty clears it)", group_id));
+                let response = ui.text_edit_singleline(&mut name);
+                if ui.memory(|memory| memory.focus().is_none()) {
+                    response.request_focus();
+                }
+                let entered = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                done = ui.button("Rename").clicked() || entered;
+            });
+
+        if done {
+            self.execute(Command::RenameGroup { group: group_id, name: Some(name) });
+        } else if open {
+            self.renaming = Some((group_id, name));
+        }
+    }
+
+    fn draw_group_properties(&mut self, ctx: &egui::Context) {
+        let Some(group) = self.group_properties.and_then(|id| self.layout.groups.get(&id)) else {
+            self.group_properties = None;
+            return;
+        };
+        let mut open = true;
+        let mut rename = false;
+        egui::Window::new(GROUP_PROPERTIES_TITLE)
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("group_properties").show(ui, |ui| {
+                    ui.label("Name");
+                    ui.label(group.display_name());
+                    ui.end_row();
+                    ui.label("Id");
+                    ui.label(group.id.to_string());
+                    ui.end_row();
+                    ui.label("Blocks");
+                    ui.label(group.blocks.len().to_string());
+                    ui.end_row();
+                    ui.label("Direction");
+                    ui.label(group.direction.map_or("Mixed".to_string(), |direction| format!("{:?}", direction)));
+                    ui.end_row();
+                    for end in [ConnectionType::Start, ConnectionType::End] {
+                        let endpoint = Endpoint { group: group.id, end };
+                        let position = self.layout.endpoint_block(endpoint)
+                            .map_or("-".to_string(), |block| format!("{:?}", block.grid_pos));
+                        let attached = match self.layout.attached_to(endpoint) {
+                            Some(Attachment::Connection(id)) => format!("connection {}", id),
+                            Some(Attachment::Turnout(id)) => format!("turnout {}", id),
+                            None => "free".to_string(),
+                        };
+                        ui.label(format!("{:?}", end));
+                        ui.label(format!("{}, {}", position, attached));
+                        ui.end_row();
+                    }
+                });
+                rename = ui.button("Rename…").clicked();
+            });
+
+        if rename {
+            self.renaming = Some((group.id, group.name.clone().unwrap_or_default()));
+        }
+        if !open {
+            self.group_properties = None;
+        }
+    }
+}
+
+// MARK: - Problems panel
+impl RailwayEditor {
+    fn draw_problems_panel(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_problems_panel;
+        let mut clicked = None;
+        egui::Window::new(PROBLEMS_PANEL_TITLE)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                if self.problems.is_empty() {
+                    ui.label("No problems found");
+                }
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for (index, diagnostic) in self.problems.iter().enumerate() {
+                        let color = match diagnostic.severity {
+                            Severity::Error => PROBLEM_ERROR_COLOR,
+                            Severity::Warning => PROBLEM_WARNING_COLOR,
+                        };
+                        let text = egui::RichText::new(diagnostic.to_string()).color(color);
+                        if ui.selectable_label(false, text).clicked() {
+                            clicked = Some(index);
+                        }
+                    }
+                });
+            });
+        self.show_problems_panel = open;
+
+        if let Some(index) = clicked {
+            self.select_problem(index);
+        }
+    }
+}
+
+// MARK: - History panel
+impl RailwayEditor {
+    // Done steps oldest first, then the steps that can be redone in grey.
+    // Clicking a step undoes or redoes up to and including it.
+    fn draw_history_panel(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_history_panel;
+        let mut undo_count = 0;
+        let mut redo_count = 0;
+        egui::Window::new(HISTORY_PANEL_TITLE)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    let done: Vec<&str> = self.history.undo_labels().collect();
+                    if done.is_empty() && !self.history.can_redo() {
+                        ui.label("Nothing to undo");
+                    }
+                    for (index, label) in done.iter().enumerate() {
+                        if ui.selectable_label(index + 1 == done.len(), *label).clicked() {
+                            undo_count = done.len() - index - 1;
+                        }
+                    }
+                    for (index, label) in self.history.redo_labels().enumerate() {
+                        let text = egui::RichText::new(label).color(HISTORY_REDO_COLOR);
+                        if ui.selectable_label(false, text).clicked() {
+                            redo_count = index + 1;
+                        }
+                    }
+                });
+            });
+        self.show_history_panel = open;
+
+        for _ in 0..undo_count {
+            self.undo();
+        }
+        for _ in 0..redo_count {
+            self.redo();
+        }
+    }
+}
+
 // MARK: - Message Box
 impl RailwayEditor {
     /*
@@ -0,0 +1,170 @@
+use eframe::egui;
+use crate::editor::RailwayEditor;
+use crate::tools::Tool;
+use crate::utils::{GRID_SIZE, MAX_ZOOM, MIN_ZOOM, ZOOM_SCROLL_SPEED, ZOOM_TO_FIT_MARGIN};
+
+// MARK: - Camera
+// Maps the layout onto the canvas. World coordinates are grid cells scaled by `GRID_SIZE`, so
+// cell (x, y) covers [x, x + 1) × [y, y + 1) times `GRID_SIZE`. On screen the world origin sits
+// `offset` pixels from the top-left of the canvas and everything is scaled by `zoom`.
+#[derive(Debug, Clone)]
+pub struct Camera {
+    // Canvas area on screen, updated every frame
+    pub viewport: egui::Rect,
+    pub offset: egui::Vec2,
+    pub zoom: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            viewport: egui::Rect::from_min_size(egui::Pos2::ZERO, egui::Vec2::ZERO),
+            offset: egui::Vec2::ZERO,
+            zoom: 1.0,
+        }
+    }
+}
+
+impl Camera {
+    pub fn world_to_screen(&self, pos: egui::Pos2) -> egui::Pos2 {
+        self.viewport.min + self.offset + pos.to_vec2() * self.zoom
+    }
+
+    pub fn screen_to_world(&self, pos: egui::Pos2) -> egui::Pos2 {
+        ((pos - self.viewport.min - self.offset) / self.zoom).to_pos2()
+    }
+
+    // Center of a cell on screen
+    pub fn grid_to_screen(&self, grid_pos: (i32, i32)) -> egui::Pos2 {
+        let center = egui::pos2((grid_pos.0 as f32 + 0.5) * GRID_SIZE, (grid_pos.1 as f32 + 0.5) * GRID_SIZE);
+        self.world_to_screen(center)
+    }
+
+    // Cell under a screen position
+    pub fn screen_to_grid(&self, pos: egui::Pos2) -> (i32, i32) {
+        let world = self.screen_to_world(pos);
+        ((world.x / GRID_SIZE).floor() as i32, (world.y / GRID_SIZE).floor() as i32)
+    }
+
+    // Part of the world showing on the canvas
+    pub fn visible_world_rect(&self) -> egui::Rect {
+        egui::Rect::from_min_max(self.screen_to_world(self.viewport.min), self.screen_to_world(self.viewport.max))
+    }
+
+    // The same view with the world origin at the screen origin, for building shapes that are
+    // moved into place later
+    pub fn at_origin(&self) -> Camera {
+        Camera {
+            viewport: egui::Rect::from_min_size(egui::Pos2::ZERO, self.viewport.size()),
+            offset: egui::Vec2::ZERO,
+            zoom: self.zoom,
+        }
+    }
+
+    // A length in world units on screen
+    pub fn scale(&self, length: f32) -> f32 {
+        length * self.zoom
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.scale(GRID_SIZE)
+    }
+
+    pub fn pan(&mut self, delta: egui::Vec2) {
+        self.offset += delta;
+    }
+
+    // Zoom by `factor`, keeping the point under `anchor` in place
+    pub fn zoom_at(&mut self, anchor: egui::Pos2, factor: f32) {
+        let world = self.screen_to_world(anchor);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.offset = anchor - self.viewport.min - world.to_vec2() * self.zoom;
+    }
+
+    // Zoom and pan so the given cells fill the canvas. Nothing changes without cells.
+    pub fn fit(&mut self, cells: impl IntoIterator<Item = (i32, i32)>) {
+        let Some(bounds) = cells.into_iter()
+            .map(|(x, y)| egui::Rect::from_min_size(egui::pos2(x as f32 * GRID_SIZE, y as f32 * GRID_SIZE), egui::Vec2::splat(GRID_SIZE)))
+            .reduce(|a, b| a.union(b))
+        else {
+            return;
+        };
+        let bounds = bounds.expand(GRID_SIZE * ZOOM_TO_FIT_MARGIN);
+        let size = self.viewport.size();
+        if size.x <= 0.0 || size.y <= 0.0 {
+            return;
+        }
+        self.zoom = (size.x / bounds.width()).min(size.y / bounds.height()).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.center_on(bounds.center());
+    }
+
+    // Pan so a world position ends up in the middle of the canvas
+    pub fn center_on(&mut self, pos: egui::Pos2) {
+        self.offset = self.viewport.size() / 2.0 - pos.to_vec2() * self.zoom;
+    }
+}
+
+// MARK: - View input
+impl RailwayEditor {
+    // Panning and zooming work in every tool: middle-drag or Space+drag pans, scrolling or
+    // pinching zooms toward the pointer. Returns true while panning or holding Space, when the
+    // active tool should leave the pointer alone.
+    pub fn handle_view_input(&mut self, ui: &mut egui::Ui, response: &egui::Response) -> bool {
+        let space = !ui.ctx().wants_keyboard_input() && ui.input(|i| i.key_down(egui::Key::Space));
+        let panning = response.dragged_by(egui::PointerButton::Middle)
+            || (space && response.dragged())
+            || (self.tool == Tool::Pan && response.dragged());
+        if panning {
+            self.camera.pan(response.drag_delta());
+        }
+
+        if response.hovered() {
+            let (scroll, pinch, pointer) = ui.input(|i| (i.scroll_delta.y, i.zoom_delta(), i.pointer.hover_pos()));
+            let factor = pinch * (scroll * ZOOM_SCROLL_SPEED).exp();
+            if let Some(pointer) = pointer.filter(|_| factor != 1.0) {
+                self.camera.zoom_at(pointer, factor);
+            }
+            if panning || space {
+                ui.ctx().set_cursor_icon(if panning { egui::CursorIcon::Grabbing } else { egui::CursorIcon::Grab });
+            }
+        }
+
+        panning || space
+    }
+
+    // Home fits the whole layout, F the selection, T toggles the render style
+    pub fn handle_view_hotkeys(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() || ctx.input(|i| i.modifiers.command) {
+            return;
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::Home)) {
+            self.zoom_to_fit();
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::F)) {
+            self.zoom_to_selection();
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::T)) {
+            self.render_style = self.render_style.toggled();
+        }
+    }
+
+    pub fn zoom_to_fit(&mut self) {
+        let cells: Vec<(i32, i32)> = self.layout.groups.values()
+            .flat_map(|group| group.blocks.iter().map(|block| block.grid_pos))
+            .collect();
+        self.camera.fit(cells);
+    }
+
+    // Falls back to the whole layout when nothing is selected
+    pub fn zoom_to_selection(&mut self) {
+        if self.selected_blocks.is_empty() {
+            self.zoom_to_fit();
+            return;
+        }
+        let cells: Vec<(i32, i32)> = self.selected_blocks.iter()
+            .filter_map(|id| self.layout.block(*id))
+            .map(|block| block.grid_pos)
+            .collect();
+        self.camera.fit(cells);
+    }
+}
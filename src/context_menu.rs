@@ -0,0 +1,134 @@
+use eframe::egui;
+use crate::editor::{RailwayEditor, SelectionMode};
+use crate::templates::TEMPLATES;
+use crate::tools::Hit;
+use railway_layout::{BlockId, Command, ConnectionId, ConnectionType, TurnoutId};
+
+// MARK: - ContextTarget
+// What a right-click on the canvas landed on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextTarget {
+    Block(BlockId),
+    Connection(ConnectionId),
+    Turnout(TurnoutId),
+    // Empty canvas at the given cell
+    Canvas((i32, i32)),
+}
+
+// MARK: - Context menu
+impl RailwayEditor {
+    // Right-clicking the canvas opens a menu for whatever is under the pointer.
+    // Every action goes through the same editor methods as the toolbar.
+    pub fn handle_context_menu(&mut self, response: &egui::Response) {
+        if response.secondary_clicked() {
+            self.context_target = response.interact_pointer_pos().map(|pos| match self.hit_test(pos) {
+                Some(Hit::Block(id)) => ContextTarget::Block(id),
+                Some(Hit::Connection(id)) => ContextTarget::Connection(id),
+                Some(Hit::Turnout(id)) => ContextTarget::Turnout(id),
+                None => ContextTarget::Canvas(self.camera.screen_to_grid(pos)),
+            });
+        }
+
+        response.clone().context_menu(|ui| match self.context_target {
+            Some(ContextTarget::Block(id)) => self.block_menu(ui, id),
+            Some(ContextTarget::Connection(id)) => self.connection_menu(ui, id),
+            Some(ContextTarget::Turnout(id)) => self.turnout_menu(ui, id),
+            Some(ContextTarget::Canvas(cell)) => self.canvas_menu(ui, cell),
+            None => ui.close_menu(),
+        });
+    }
+
+    fn block_menu(&mut self, ui: &mut egui::Ui, block_id: BlockId) {
+        let Some(group) = self.layout.group_of_block(block_id) else {
+            ui.close_menu();
+            return;
+        };
+        let group_id = group.id;
+        let name = group.display_name();
+        let splittable = group.blocks.len() > 1;
+        let end = self.layout.endpoint_of_block(block_id).ok().filter(|_| splittable).map(|endpoint| endpoint.end);
+
+        if ui.button("Delete").clicked() {
+            self.erase_blocks(vec![block_id]);
+            ui.close_menu();
+        }
+        if ui.button("Select Group").clicked() {
+            self.select_group(block_id, SelectionMode::Replace);
+            ui.close_menu();
+        }
+        for (target, text) in [(ConnectionType::Start, "Set as Start"), (ConnectionType::End, "Set as End")] {
+            let enabled = end.is_some_and(|end| end != target);
+            if ui.add_enabled(enabled, egui::Button::new(text)).clicked() {
+                self.execute(Command::SetGroupEnd { block: block_id, end: target });
+                ui.close_menu();
+            }
+        }
+
+        ui.separator();
+        ui.label(name.clone());
+        if ui.button("Rename…").clicked() {
+            self.renaming = Some((group_id, self.layout.groups.get(&group_id).and_then(|group| group.name.clone()).unwrap_or_default()));
+            ui.close_menu();
+        }
+        if ui.button("Properties").clicked() {
+            self.group_properties = Some(group_id);
+            ui.close_menu();
+        }
+        if ui.add_enabled(splittable, egui::Button::new("Split Here")).on_hover_text("Cut the group after this block").clicked() {
+            self.execute(Command::SplitGroupAt(block_id));
+            self.selected_blocks.clear();
+            ui.close_menu();
+        }
+    }
+
+    fn connection_menu(&mut self, ui: &mut egui::Ui, connection_id: ConnectionId) {
+        if self.layout.connections.get(connection_id).is_none() {
+            ui.close_menu();
+            return;
+        }
+        if ui.button("Edit…").clicked() {
+            self.selected_connection = Some(connection_id);
+            self.selected_blocks.clear();
+            ui.close_menu();
+        }
+        if ui.button("Reverse").clicked() {
+            self.reverse_connection(connection_id);
+            ui.close_menu();
+        }
+        if ui.button("Delete").clicked() {
+            self.selected_connection = Some(connection_id);
+            self.delete_selection();
+            ui.close_menu();
+        }
+    }
+
+    fn turnout_menu(&mut self, ui: &mut egui::Ui, turnout_id: TurnoutId) {
+        if self.layout.turnouts.get(turnout_id).is_none() {
+            ui.close_menu();
+            return;
+        }
+        if ui.button("Throw").clicked() {
+            self.toggle_turnout(turnout_id);
+            ui.close_menu();
+        }
+        if ui.button("Delete").clicked() {
+            self.execute(Command::RemoveTurnout(turnout_id));
+            ui.close_menu();
+        }
+    }
+
+    fn canvas_menu(&mut self, ui: &mut egui::Ui, cell: (i32, i32)) {
+        if ui.button("Paste").clicked() {
+            self.paste_from_clipboard(cell);
+            ui.close_menu();
+        }
+        ui.menu_button("Place Template", |ui| {
+            for template in &TEMPLATES {
+                if ui.button(template.name).clicked() {
+                    self.place_template(template, cell);
+                    ui.close_menu();
+                }
+            }
+        });
+    }
+}
@@ -1,44 +1,125 @@
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use crate::models::{Connection, ConnectionType, Direction, Group};
+use eframe::egui;
+use railway_layout::{
+    BlockId, BlockKind, Command, ConnectionId, Diagnostic, Direction, Endpoint, Fragment, GroupId, Hand, History, Layout, LayoutError, Outcome, Port, Transform, TurnoutId,
+};
 use indexmap::IndexSet;
+use crate::camera::Camera;
+use crate::minimap::Minimap;
+use crate::rendering::{CanvasCache, RenderStyle};
+use crate::context_menu::ContextTarget;
+use crate::templates::Template;
+use crate::tools::{ConnectMode, Tool};
 
-// MARK: - AppMode
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub enum AppMode {
-    Normal,
-    SetConnections
+// MARK: - SelectionMode
+// How a marquee or group selection combines with the current selection
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SelectionMode {
+    Replace,
+    Add,
+    Subtract,
 }
 
+// MARK: - Suggestion
+// A connection proposed by Connect Start, waiting for review
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub from: Endpoint,
+    pub to: Endpoint,
+    pub picked: bool,
+}
+
+// Track pieces offered for placement, in their default orientation
+pub const TRACK_PIECES: [BlockKind; 7] = [
+    BlockKind::Straight(Direction::Horizontal),
+    BlockKind::Straight(Direction::DiagonalUp),
+    BlockKind::Curve(Port::South),
+    BlockKind::Turnout { points: Port::West, hand: Hand::Left },
+    BlockKind::Turnout { points: Port::West, hand: Hand::Right },
+    BlockKind::Crossing,
+    BlockKind::BufferStop(Port::East),
+];
+
 // MARK: - RailwayEditor
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug)]
 pub struct RailwayEditor {
-    pub groups: HashMap<u32, Group>,
-    pub next_block_id: u32,
-    pub next_group_id: u32,
-    #[serde(skip)]
-    pub selected_blocks: IndexSet<u32>,
-    #[serde(skip)]
-    pub show_connection_panel: bool,
-    pub app_mode:AppMode,
-    #[serde(skip)]
+    pub layout: Layout,
+    pub camera: Camera,
+    pub tool: Tool,
+    pub selected_blocks: IndexSet<BlockId>,
+    // Connection picked on the canvas, shown in the Connection window
+    pub selected_connection: Option<ConnectionId>,
+    // Screen position where the current marquee drag started
+    pub marquee_start: Option<egui::Pos2>,
+    // Screen position where dragging the selection started
+    pub move_start: Option<egui::Pos2>,
+    // Piece placed on click; `None` places straights oriented along their neighbors
+    pub placing_kind: Option<BlockKind>,
+    // Cell where the current run of track starts
+    pub line_start: Option<(i32, i32)>,
+    // Blocks swept by the current erase drag
+    pub erasing: Vec<BlockId>,
+    // Cell where the current measurement starts
+    pub measure_start: Option<(i32, i32)>,
+    // Group end the current connection drag started from
+    pub connect_from: Option<Endpoint>,
+    pub connect_mode: ConnectMode,
+    // What the open context menu was opened on
+    pub context_target: Option<ContextTarget>,
+    // Group being renamed and the name typed so far
+    pub renaming: Option<(GroupId, String)>,
+    // Group shown in the Group Properties window
+    pub group_properties: Option<GroupId>,
+    // Connections proposed between touching group ends, listed for review
+    pub suggestions: Vec<Suggestion>,
+    // New connections can be travelled both ways
+    pub connect_bidirectional: bool,
     pub show_message_box:bool,
-    #[serde(skip)]
     pub message: String,
+    // Result of the last `validate` run, listed in the Problems panel
+    pub problems: Vec<Diagnostic>,
+    pub show_problems_panel: bool,
+    // Every edit of `layout` goes through here so it can be undone
+    pub history: History,
+    pub show_history_panel: bool,
+    pub minimap: Minimap,
+    pub show_minimap: bool,
+    // Prepared shapes of the groups, see `draw_blocks`
+    pub canvas_cache: CanvasCache,
+    pub render_style: RenderStyle,
 }
 
 // MARK: - RailwayEditor - Default
 impl Default for RailwayEditor {
     fn default() -> Self {
         Self {
-            groups: HashMap::new(),
-            next_block_id: 1,
-            next_group_id: 1,
+            layout: Layout::default(),
+            camera: Camera::default(),
+            tool: Tool::Select,
             selected_blocks: IndexSet::new(),
-            show_connection_panel: false,
-            app_mode: AppMode::Normal,
+            selected_connection: None,
+            marquee_start: None,
+            move_start: None,
+            placing_kind: None,
+            line_start: None,
+            erasing: Vec::new(),
+            measure_start: None,
+            connect_from: None,
+            connect_mode: ConnectMode::Connections,
+            context_target: None,
+            renaming: None,
+            group_properties: None,
+            suggestions: Vec::new(),
+            connect_bidirectional: false,
             show_message_box: false,
             message: String::new(),
+            problems: Vec::new(),
+            show_problems_panel: false,
+            history: History::default(),
+            show_history_panel: false,
+            minimap: Minimap::default(),
+            show_minimap: true,
+            canvas_cache: CanvasCache::default(),
+            render_style: RenderStyle::Cells,
         }
     }
 }
@@ -47,209 +128,361 @@ impl Default for RailwayEditor {
 impl RailwayEditor {
     // Remove selected blocks from all groups
     pub fn remove_selected_blocks(&mut self) {
-        for group in self.groups.values_mut() {
-            group.blocks.retain(|block| !self.selected_blocks.contains(&block.id));
-            group.update_start_end_blocks(); // Update start and end blocks
+        if self.selected_blocks.is_empty() {
+            return;
         }
-        // Remove empty groups
-        self.groups.retain(|_, group| !group.blocks.is_empty());
+        let selected: Vec<BlockId> = self.selected_blocks.iter().copied().collect();
+        self.execute(Command::RemoveBlocks(selected));
         self.selected_blocks.clear();
+        self.drop_stale_selection();
     }
 
-    // Find groups that have blocks neighboring the given position
-    pub fn find_neighboring_groups(&self, pos: (i32, i32)) -> Vec<u32> {
-        let mut neighbors = Vec::new();
-        let directions = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+    // Remove the given blocks, e.g. those swept by the Erase tool
+    pub fn erase_blocks(&mut self, blocks: Vec<BlockId>) {
+        if self.execute(Command::RemoveBlocks(blocks)).is_some() {
+            self.drop_stale_selection();
+        }
+    }
 
-        for (dx, dy) in directions {
-            let neighbor_pos = (pos.0 + dx, pos.1 + dy);
-            for (id, group) in &self.groups {
-                if group.blocks.iter().any(|block| block.grid_pos == neighbor_pos) 
-                    && !neighbors.contains(id) {
-                    neighbors.push(*id);
-                }
-            }
+    // Place a block at the given grid position
+    pub fn place_block(&mut self, grid_pos: (i32, i32)) {
+        if self.layout.is_occupied(grid_pos) {
+            return;
         }
-        neighbors
-    }
-
-    // Update the direction of a group based on its blocks
-    pub fn update_group_direction(&mut self, group_id: u32) {
-        if let Some(group) = self.groups.get_mut(&group_id) {
-            if group.blocks.len() >= 2 {
-                let first = &group.blocks[0];
-                let second = &group.blocks[1];
-                
-                group.direction = Some(if first.grid_pos.0 == second.grid_pos.0 {
-                    Direction::Vertical
-                } else {
-                    Direction::Horizontal
-                });
-            } else {
-                group.direction = None;
-            }
+        self.execute(Command::PlaceBlock { grid_pos, kind: self.placing_kind });
+    }
+
+    // Lay a straight run of track between two cells
+    pub fn lay_run(&mut self, start: (i32, i32), end: (i32, i32)) {
+        self.execute(Command::LayRun { start, end });
+    }
+
+    // Turn the piece to be placed by 90° clockwise
+    pub fn rotate_placing_kind(&mut self) {
+        if let Some(kind) = self.placing_kind {
+            self.placing_kind = Some(kind.transformed(Transform::RotateCw));
         }
     }
 
-    // Check if a block can be added to a group based on its direction
-    pub fn can_add_to_group(&self, group_id: u32, pos: (i32, i32)) -> bool {
-        if let Some(group) = self.groups.get(&group_id) {
-            if let (Some(direction), Some(last_block)) = (&group.direction, group.blocks.last()) {
-                match direction {
-                    Direction::Horizontal => pos.1 == last_block.grid_pos.1,
-                    Direction::Vertical => pos.0 == last_block.grid_pos.0,
-                }
-            } else {
-                true
-            }
-        } else {
-            false
+    // Connect two group ends, one way unless bidirectional connections are chosen
+    pub fn connect(&mut self, from: Endpoint, to: Endpoint) {
+        self.execute(Command::Connect { from, to, directed: !self.connect_bidirectional });
+    }
+
+    // Renumber all ids densely, keeping the current selection
+    pub fn compact_ids(&mut self) {
+        if let Some(Outcome::Compacted(mapping)) = self.execute(Command::CompactIds) {
+            self.selected_blocks = self.selected_blocks.iter()
+                .filter_map(|id| mapping.blocks.get(id).copied())
+                .collect();
+            self.selected_connection = self.selected_connection.and_then(|id| mapping.connections.get(&id).copied());
         }
     }
 
-    // Merge two groups into one
-    pub fn merge_groups(&mut self, group1_id: u32, group2_id: u32) {
-        if group1_id == group2_id {
-            return;
+    // Add a turnout from the three selected endpoint blocks: points, normal leg, reverse leg.
+    // Only called once the reverse leg is clicked again to confirm.
+    pub fn add_turnout(&mut self) {
+        if self.selected_blocks.len() == 3 {
+            let points = self.selected_blocks[0];
+            let normal = self.selected_blocks[1];
+            let reverse = self.selected_blocks[2];
+
+            self.execute(Command::AddTurnout { points, normal, reverse });
+            self.selected_blocks.clear();
+            self.connect_mode = ConnectMode::Connections;
         }
-        
-        if let Some(group1) = self.groups.remove(&group1_id) {
-            if let Some(group2) = self.groups.get_mut(&group2_id) {
-                group2.blocks.extend(group1.blocks);
-                group2.connections.extend(group1.connections);
-                group2.update_start_end_blocks(); // Update start and end blocks
-                self.update_group_direction(group2_id);
+    }
+
+    pub fn toggle_turnout(&mut self, turnout_id: TurnoutId) {
+        self.execute(Command::ToggleTurnout(turnout_id));
+    }
+
+    pub fn clear_connections(&mut self) {
+        self.execute(Command::ClearConnections);
+        self.selected_connection = None;
+    }
+
+    // Delete removes the selected connection, or else the selected blocks
+    pub fn delete_selection(&mut self) {
+        match self.selected_connection.take() {
+            Some(id) => {
+                self.execute(Command::RemoveConnection(id));
             }
+            None => self.remove_selected_blocks(),
         }
-        self.reindex_groups();
-    }
-
-    // Connect two groups
-    pub fn connect_groups(&mut self) {
-        if self.selected_blocks.len() == 2 {
-            let mut connection = Connection::default();
-
-            eprintln!("0th selected block id: {}", *self.selected_blocks.first().unwrap());
-            eprintln!("1th selected block id: {}", *self.selected_blocks.last().unwrap());
-
-            let from_element = *self.selected_blocks.first().unwrap();
-            let to_element = *self.selected_blocks.last().unwrap();
-            for group in self.groups.values() {
-                if let (Some(start_id), Some(end_id)) = (group.start_block_id, group.end_block_id) {
-                    // check from element
-                    if from_element == start_id || from_element == end_id {
-                        connection.from_group = group.id;
-                        connection.from_connection_type = if from_element == start_id { ConnectionType::Start } else { ConnectionType::End };
-                    }
-
-                    // check to element
-                    if to_element == start_id || to_element == end_id {
-                        connection.to_group = group.id;
-                        connection.to_connection_type = if to_element == start_id { ConnectionType::Start } else { ConnectionType::End };
-                    }
-                } 
-            }
+    }
 
-            eprintln!("{:?}", connection);
-
-            // PUSH connection
-            for group in self.groups.values_mut() {
-                if group.id == connection.from_group {
-                    let mut connection_exists: bool = false;
-
-                    // Check existing connections
-                    for ex_connection in &group.connections {
-                        if ex_connection.from_group == connection.from_group
-                            && ex_connection.to_group == connection.to_group
-                            && ex_connection.from_connection_type == connection.from_connection_type
-                            && ex_connection.to_connection_type == connection.to_connection_type
-                        {
-                            connection_exists = true;
-                            break;
-                        }
-                    }
-
-                    // if connection does not exists add it to group's connections
-                    if !connection_exists {
-                        group.connections.push(connection);
-                        eprintln!("{:?}", group);
-                        break;
-                    } else {
-                        // show message box
-                        self.message = "Given connection already exists".to_string();
-                        self.show_message_box = true;
-                        break;
-                    }
-                }
-            }
-            self.selected_blocks.clear();
+    // Propose connections wherever free ends of different groups touch
+    pub fn suggest_connections(&mut self) {
+        self.suggestions = self.layout.suggest_connections().into_iter()
+            .map(|(from, to)| Suggestion { from, to, picked: true })
+            .collect();
+        if self.suggestions.is_empty() {
+            self.show_message("No touching group ends to connect");
+        }
+    }
+
+    // Add the suggested connections, all of them or only the picked ones, in one step
+    pub fn accept_suggestions(&mut self, only_picked: bool) {
+        let pairs: Vec<_> = std::mem::take(&mut self.suggestions).into_iter()
+            .filter(|suggestion| suggestion.picked || !only_picked)
+            .map(|suggestion| (suggestion.from, suggestion.to))
+            .collect();
+        if !pairs.is_empty() {
+            self.execute(Command::ConnectMany { pairs, directed: !self.connect_bidirectional });
         }
     }
+
+    pub fn reject_suggestions(&mut self) {
+        self.suggestions.clear();
+    }
+
+    pub fn reverse_connection(&mut self, id: ConnectionId) {
+        self.execute(Command::ReverseConnection(id));
+    }
+
+    pub fn edit_connection(&mut self, id: ConnectionId, from: Endpoint, to: Endpoint, directed: bool) {
+        self.execute(Command::EditConnection { id, from, to, directed });
+    }
+
+    pub fn show_message(&mut self, message: impl Into<String>) {
+        self.message = message.into();
+        self.show_message_box = true;
+    }
 }
 
+// MARK: - History
+impl RailwayEditor {
+    // Run an edit through the history, reporting a failure to the user
+    pub fn execute(&mut self, command: Command) -> Option<Outcome> {
+        match self.history.execute(&mut self.layout, command) {
+            Ok(outcome) => Some(outcome),
+            Err(e) => {
+                self.show_message(e.to_string());
+                None
+            }
+        }
+    }
+
+    pub fn undo(&mut self) {
+        if self.history.undo(&mut self.layout).is_some() {
+            self.drop_stale_selection();
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if self.history.redo(&mut self.layout).is_some() {
+            self.drop_stale_selection();
+        }
+    }
+
+    // Forget selected blocks and connections that no longer exist
+    fn drop_stale_selection(&mut self) {
+        let layout = &self.layout;
+        self.selected_blocks.retain(|id| layout.group_of_block(*id).is_some());
+        self.selected_connection = self.selected_connection.filter(|id| layout.connections.get(*id).is_some());
+    }
+}
 
 // MARK: - Layout edit
 impl RailwayEditor {
     // Save the current layout to a JSON file
     pub fn save_layout(&mut self) {
-        if let Ok(serialized) = serde_json::to_string_pretty(&self) {
-            if let Err(e) = std::fs::write("layout.json", serialized) {
-                eprintln!("Failed to save layout: {}", e);
-                self.message = "Failed to save layout".to_string();
-                self.show_message_box = true;
-            }
-        } else {
-            self.message = "Failed to serialize layout".to_string();
-            self.show_message_box = true;
+        if let Err(e) = self.layout.save("layout.json") {
+            eprintln!("Failed to save layout: {}", e);
+            self.show_message("Failed to save layout");
         }
     }
 
     // Load a layout from a JSON file
     pub fn load_layout(&mut self) {
-        match std::fs::read_to_string("layout.json") {
-            Ok(contents) => {
-                if let Ok(loaded) = serde_json::from_str::<RailwayEditor>(&contents) {
-                    self.groups = loaded.groups;
-                    self.next_block_id = loaded.next_block_id;
-                    self.next_group_id = loaded.next_group_id;
-                    self.selected_blocks.clear();
-                } else {
-                    self.message = "Failed to deserialize layout".to_string();
-                    self.show_message_box = true;
+        match Layout::load("layout.json") {
+            Ok(loaded) => {
+                self.execute(Command::Load(Box::new(loaded)));
+                self.selected_blocks.clear();
+                self.selected_connection = None;
+                self.check_layout();
+            }
+            Err(LayoutError::Io(e)) => eprintln!("Failed to load layout: {}", e),
+            Err(e @ LayoutError::NewerFormat { .. }) => self.show_message(e.to_string()),
+            Err(_) => self.show_message("Failed to deserialize layout"),
+        }
+    }
+}
+
+// MARK: - Selection
+impl RailwayEditor {
+    pub fn select_blocks(&mut self, blocks: impl IntoIterator<Item = BlockId>, mode: SelectionMode) {
+        match mode {
+            SelectionMode::Replace => self.selected_blocks = blocks.into_iter().collect(),
+            SelectionMode::Add => self.selected_blocks.extend(blocks),
+            SelectionMode::Subtract => {
+                for block in blocks {
+                    self.selected_blocks.shift_remove(&block);
                 }
             }
-            Err(e) => eprintln!("Failed to load layout: {}", e),
-        }
-    }
-
-    fn reindex_groups(&mut self) {
-        let mut new_groups = HashMap::new();
-        let old_ids: Vec<u32> = self.groups.keys().cloned().collect();
-        let mut id_mapping = HashMap::new();
-        
-        // Create mapping
-        for (i, old_id) in old_ids.iter().enumerate() {
-            id_mapping.insert(*old_id, (i + 1) as u32);
-        }
-        
-        // Create new groups with updated IDs
-        for (old_id, group) in &self.groups {
-            let new_id = id_mapping[old_id];
-            let mut new_group = group.clone();
-            
-            // Update group ID
-            new_group.id = new_id;
-            
-            // Update connections (TODO: Crash when trying to merge groups after having some connections)
-            for conn in &mut new_group.connections {
-                conn.from_group = id_mapping[&conn.from_group];
-                conn.to_group = id_mapping[&conn.to_group];
+        }
+    }
+
+    // Select every block of the group the given block belongs to
+    pub fn select_group(&mut self, block_id: BlockId, mode: SelectionMode) {
+        let blocks: Vec<BlockId> = self.layout.group_of_block(block_id)
+            .map(|group| group.blocks.iter().map(|block| block.id).collect())
+            .unwrap_or_default();
+        self.selected_connection = None;
+        self.select_blocks(blocks, mode);
+    }
+
+    // Move the selected blocks by the given number of grid cells
+    pub fn move_selected_blocks(&mut self, offset: (i32, i32)) {
+        if self.selected_blocks.is_empty() || offset == (0, 0) {
+            return;
+        }
+        let blocks: Vec<BlockId> = self.selected_blocks.iter().copied().collect();
+        self.execute(Command::MoveBlocks { blocks, offset });
+    }
+
+    // Rotate or mirror the selected blocks within their bounding box
+    pub fn transform_selected_blocks(&mut self, transform: Transform) {
+        if self.selected_blocks.is_empty() {
+            return;
+        }
+        let blocks: Vec<BlockId> = self.selected_blocks.iter().copied().collect();
+        self.execute(Command::TransformBlocks { blocks, transform });
+    }
+
+    // Extend the selection to every block of every group reachable through connections
+    // and turnouts from the groups already selected
+    pub fn select_connected(&mut self) {
+        let mut start: Vec<GroupId> = self.selected_blocks.iter()
+            .filter_map(|id| self.layout.group_of_block(*id))
+            .map(|group| group.id)
+            .collect();
+        start.sort();
+        start.dedup();
+
+        let blocks: Vec<BlockId> = self.layout.connected_groups(&start).into_iter()
+            .filter_map(|id| self.layout.groups.get(&id))
+            .flat_map(|group| group.blocks.iter().map(|block| block.id))
+            .collect();
+        self.select_blocks(blocks, SelectionMode::Add);
+    }
+}
+
+// MARK: - Clipboard
+impl RailwayEditor {
+    // Put the selected blocks on the system clipboard as a layout fragment.
+    // Returns whether anything was copied.
+    pub fn copy_selection(&mut self, ctx: &egui::Context) -> bool {
+        if self.selected_blocks.is_empty() {
+            return false;
+        }
+        let selected: Vec<BlockId> = self.selected_blocks.iter().copied().collect();
+        match self.layout.copy_fragment(&selected).and_then(|fragment| fragment.to_json()) {
+            Ok(json) => {
+                ctx.output_mut(|o| o.copied_text = json);
+                true
+            }
+            Err(e) => {
+                self.show_message(e.to_string());
+                false
             }
-            
-            new_groups.insert(new_id, new_group);
         }
-        
-        self.groups = new_groups;
-        self.next_group_id = (self.groups.len() + 1) as u32;
     }
-}
\ No newline at end of file
+
+    // The blocks are only removed once they are on the clipboard
+    pub fn cut_selection(&mut self, ctx: &egui::Context) {
+        if self.copy_selection(ctx) {
+            self.remove_selected_blocks();
+        }
+    }
+
+    // Paste clipboard text with its top-left corner at the given cell and select the result.
+    // Text that is not a layout fragment is ignored.
+    pub fn paste(&mut self, text: &str, origin: (i32, i32)) {
+        let fragment = match Fragment::from_json(text) {
+            Ok(fragment) => fragment,
+            Err(e @ LayoutError::NewerFormat { .. }) => {
+                self.show_message(e.to_string());
+                return;
+            }
+            Err(_) => return,
+        };
+        if fragment.is_empty() {
+            return;
+        }
+        self.paste_fragment(fragment, origin);
+    }
+
+    // Paste a fragment and select what was added. Connections and turnouts that could not be
+    // added are reported rather than dropped silently.
+    fn paste_fragment(&mut self, fragment: Fragment, origin: (i32, i32)) {
+        let Some(Outcome::Pasted(pasted)) = self.execute(Command::Paste { fragment, origin }) else {
+            return;
+        };
+        let (connections, turnouts) = (pasted.skipped_connections.len(), pasted.skipped_turnouts.len());
+        self.select_blocks(pasted.blocks, SelectionMode::Replace);
+        if connections + turnouts > 0 {
+            self.show_message(format!(
+                "{connections} connection(s) and {turnouts} turnout(s) of the pasted track could not be added"
+            ));
+        }
+    }
+
+    // Paste what is on the system clipboard, as Ctrl+V does, for the context menu where there
+    // is no paste event carrying the text. Fragments copied in another window paste here too.
+    pub fn paste_from_clipboard(&mut self, origin: (i32, i32)) {
+        match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.get_text()) {
+            Ok(text) => self.paste(&text, origin),
+            Err(e) => self.show_message(format!("Cannot read the clipboard: {e}")),
+        }
+    }
+
+    // Paste a built-in template with its top-left corner at the given cell and select it
+    pub fn place_template(&mut self, template: &Template, origin: (i32, i32)) {
+        match template.fragment() {
+            Ok(fragment) => {
+                self.paste_fragment(fragment, origin);
+            }
+            Err(e) => self.show_message(e.to_string()),
+        }
+    }
+
+    // Paste a copy of the selection just right of it
+    pub fn duplicate_selection(&mut self) {
+        let selected: Vec<BlockId> = self.selected_blocks.iter().copied().collect();
+        let positions: Vec<(i32, i32)> = selected.iter()
+            .filter_map(|id| self.layout.block(*id))
+            .map(|block| block.grid_pos)
+            .collect();
+        let (Some(min_x), Some(min_y)) = (positions.iter().map(|p| p.0).min(), positions.iter().map(|p| p.1).min()) else {
+            return;
+        };
+
+        match self.layout.copy_fragment(&selected) {
+            Ok(fragment) => {
+                let origin = (min_x + fragment.size().0 + 1, min_y);
+                self.paste_fragment(fragment, origin);
+            }
+            Err(e) => self.show_message(e.to_string()),
+        }
+    }
+}
+
+// MARK: - Problems
+impl RailwayEditor {
+    // Validate the layout and open the Problems panel if anything is wrong
+    pub fn check_layout(&mut self) {
+        self.problems = self.layout.validate();
+        self.show_problems_panel = !self.problems.is_empty();
+    }
+
+    // Select the blocks a problem refers to. Switches to the Select tool first,
+    // since the Connect tool acts on whatever endpoints end up selected.
+    pub fn select_problem(&mut self, index: usize) {
+        if let Some(blocks) = self.problems.get(index).map(|problem| problem.blocks.clone()) {
+            self.set_tool(Tool::Select);
+            self.selected_blocks = blocks.into_iter().collect();
+        }
+    }
+}
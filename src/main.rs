@@ -1,9 +1,14 @@
 
 
 mod app;
+mod camera;
+mod context_menu;
 mod editor;
-mod models;
+mod minimap;
 mod rendering;
+mod schematic;
+mod templates;
+mod tools;
 mod utils;
 
 use crate::editor::RailwayEditor;
@@ -0,0 +1,171 @@
+use eframe::egui;
+use egui::{Pos2, Rect, Shape};
+use std::collections::HashSet;
+use crate::editor::RailwayEditor;
+use crate::utils::*;
+use railway_layout::{Group, GroupCache, History, Layout};
+
+// MARK: - Minimap
+// Thumbnail of the whole layout. Shapes are kept per group and only rebuilt for groups that
+// changed, or all at once when the thumbnail has to be rescaled.
+#[derive(Debug, Default)]
+pub struct Minimap {
+    groups: GroupCache<MinimapGroup>,
+    connections: Vec<Shape>,
+    connections_revision: Option<u64>,
+    transform: Option<MinimapTransform>,
+}
+
+#[derive(Debug)]
+struct MinimapGroup {
+    // Area covered by the group's cells, in world coordinates
+    bounds: Rect,
+    // Built lazily for the current transform
+    shape: Option<Shape>,
+}
+
+// Maps world coordinates into the minimap
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MinimapTransform {
+    world: Rect,
+    screen: Rect,
+    scale: f32,
+}
+
+impl MinimapTransform {
+    // Fit `world` into `screen`, centered
+    fn fit(world: Rect, screen: Rect) -> Self {
+        let scale = (screen.width() / world.width()).min(screen.height() / world.height());
+        let world = Rect::from_center_size(world.center(), screen.size() / scale);
+        Self { world, screen, scale }
+    }
+
+    fn to_screen(self, pos: Pos2) -> Pos2 {
+        self.screen.min + (pos - self.world.min) * self.scale
+    }
+
+    fn to_world(self, pos: Pos2) -> Pos2 {
+        self.world.min + (pos - self.screen.min) / self.scale
+    }
+
+    fn rect_to_screen(self, rect: Rect) -> Rect {
+        Rect::from_min_max(self.to_screen(rect.min), self.to_screen(rect.max))
+    }
+}
+
+impl Minimap {
+    // Catch up with the layout and the minimap's place on screen
+    fn update(&mut self, layout: &Layout, history: &History, screen: Rect, viewport: Rect) {
+        let revision = history.revision();
+        self.groups.sync(layout, history, |group| MinimapGroup { bounds: group_bounds(group), shape: None });
+
+        // An empty layout just shows the view
+        let world = self.groups.values()
+            .map(|group| group.bounds)
+            .reduce(|a, b| a.union(b))
+            .map_or(viewport, |bounds| bounds.expand(GRID_SIZE * ZOOM_TO_FIT_MARGIN));
+        let transform = MinimapTransform::fit(world, screen);
+        if self.transform != Some(transform) {
+            self.transform = Some(transform);
+            self.connections_revision = None;
+            for (_, group) in self.groups.iter_mut() {
+                group.shape = None;
+            }
+        }
+
+        for (id, group) in self.groups.iter_mut() {
+            if group.shape.is_none() {
+                group.shape = layout.groups.get(&id).map(|source| group_shape(source, transform));
+            }
+        }
+
+        if self.connections_revision != Some(revision) {
+            self.connections_revision = Some(revision);
+            self.connections = layout.connections.iter()
+                .filter_map(|connection| {
+                    let from = layout.endpoint_block(connection.from)?;
+                    let to = layout.endpoint_block(connection.to)?;
+                    Some(Shape::line_segment(
+                        [transform.to_screen(cell_center(from.grid_pos)), transform.to_screen(cell_center(to.grid_pos))],
+                        egui::Stroke::new(MINIMAP_CONNECTION_STROKE_WIDTH, CONNECTION_ARROW_COLOR),
+                    ))
+                })
+                .collect();
+        }
+    }
+}
+
+fn cell_rect(grid_pos: (i32, i32)) -> Rect {
+    Rect::from_min_size(
+        egui::pos2(grid_pos.0 as f32 * GRID_SIZE, grid_pos.1 as f32 * GRID_SIZE),
+        egui::Vec2::splat(GRID_SIZE),
+    )
+}
+
+fn cell_center(grid_pos: (i32, i32)) -> Pos2 {
+    cell_rect(grid_pos).center()
+}
+
+fn group_bounds(group: &Group) -> Rect {
+    group.blocks.iter()
+        .map(|block| cell_rect(block.grid_pos))
+        .reduce(|a, b| a.union(b))
+        .unwrap_or(Rect::NOTHING)
+}
+
+// One quad per block, at least a pixel across so single cells stay visible. Where cells are
+// smaller than a pixel, blocks sharing a pixel share a quad.
+fn group_shape(group: &Group, transform: MinimapTransform) -> Shape {
+    let mut mesh = egui::Mesh::default();
+    let mut pixels = HashSet::new();
+    for block in &group.blocks {
+        let rect = transform.rect_to_screen(cell_rect(block.grid_pos));
+        if rect.width() < 1.0 {
+            let pixel = rect.center().floor();
+            if pixels.insert((pixel.x as i32, pixel.y as i32)) {
+                mesh.add_colored_rect(Rect::from_min_size(pixel, egui::Vec2::splat(1.0)), DEFAULT_BLOCK_COLOR);
+            }
+        } else {
+            mesh.add_colored_rect(rect, DEFAULT_BLOCK_COLOR);
+        }
+    }
+    Shape::mesh(mesh)
+}
+
+// MARK: - Minimap window
+impl RailwayEditor {
+    // Clicking or dragging in the minimap centers the canvas on that spot
+    pub fn draw_minimap(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_minimap;
+        egui::Window::new(MINIMAP_TITLE)
+            .open(&mut open)
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-8.0, -8.0))
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let (rect, response) = ui.allocate_exact_size(MINIMAP_SIZE, egui::Sense::click_and_drag());
+                let viewport = self.camera.visible_world_rect();
+                self.minimap.update(&self.layout, &self.history, rect, viewport);
+                let Some(transform) = self.minimap.transform else {
+                    return;
+                };
+
+                if response.clicked() || response.dragged() {
+                    if let Some(pos) = response.interact_pointer_pos() {
+                        self.camera.center_on(transform.to_world(pos));
+                    }
+                }
+
+                let painter = ui.painter_at(rect);
+                painter.rect_filled(rect, 0.0, MINIMAP_BACKGROUND_COLOR);
+                painter.extend(self.minimap.groups.values().filter_map(|group| group.shape.clone()));
+                painter.extend(self.minimap.connections.iter().cloned());
+                painter.rect_stroke(
+                    transform.rect_to_screen(viewport),
+                    0.0,
+                    egui::Stroke::new(MINIMAP_VIEWPORT_STROKE_WIDTH, MINIMAP_VIEWPORT_COLOR),
+                );
+            });
+        self.show_minimap = open;
+    }
+}
@@ -1,7 +0,0 @@
-pub mod block;
-pub mod connection;
-pub mod group;
-
-pub use block::Block;
-pub use connection::{Connection, ConnectionType};
-pub use group::{Group, Direction};
\ No newline at end of file
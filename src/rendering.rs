@@ -1,111 +1,590 @@
 use eframe::egui;
 use egui::{Color32, Pos2};
-use crate::editor::{RailwayEditor, AppMode};
-use crate::models::ConnectionType;
-use crate::utils::{GRID_SIZE, BLOCK_SIZE, grid_to_screen};
+use crate::editor::RailwayEditor;
+use railway_layout::{BlockId, BlockKind, Connection, ConnectionType, Direction, Endpoint, Group, GroupCache, History, Layout, Port, Turnout};
+use crate::camera::Camera;
+use crate::schematic;
 use crate::utils::*;
 
-pub fn draw_grid(painter: &egui::Painter, rect: egui::Rect) {
-    // Draw vertical lines
-    let mut x = rect.left();
+// Grid lines follow the camera and are sent as a single mesh. Zoomed far out only every
+// `GRID_LOD_STEP`th line is kept, so lines never get closer than `MIN_GRID_LINE_SPACING`.
+pub fn draw_grid(painter: &egui::Painter, camera: &Camera) {
+    let rect = camera.viewport;
+    let mut spacing = camera.cell_size();
+    while spacing < MIN_GRID_LINE_SPACING {
+        spacing *= GRID_LOD_STEP;
+    }
+    let origin = camera.world_to_screen(Pos2::ZERO);
+    let half_width = GRID_LINE_STROKE_WIDTH / 2.0;
+    let mut mesh = egui::Mesh::default();
+
+    // Vertical lines
+    let mut x = rect.left() + (origin.x - rect.left()).rem_euclid(spacing);
     while x <= rect.right() {
-        painter.line_segment(
-            [egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())],
-            egui::Stroke::new(GRID_LINE_STROKE_WIDTH, GRID_LINE_COLOR),
+        mesh.add_colored_rect(
+            egui::Rect::from_x_y_ranges(x - half_width..=x + half_width, rect.y_range()),
+            GRID_LINE_COLOR,
         );
-        x += GRID_SIZE;
+        x += spacing;
     }
-    
-    // Draw horizontal lines
-    let mut y = rect.top();
+
+    // Horizontal lines
+    let mut y = rect.top() + (origin.y - rect.top()).rem_euclid(spacing);
     while y <= rect.bottom() {
-        painter.line_segment(
-            [egui::pos2(rect.left(), y), egui::pos2(rect.right(), y)],
-            egui::Stroke::new(GRID_LINE_STROKE_WIDTH, GRID_LINE_COLOR),
+        mesh.add_colored_rect(
+            egui::Rect::from_x_y_ranges(rect.x_range(), y - half_width..=y + half_width),
+            GRID_LINE_COLOR,
         );
-        y += GRID_SIZE;
+        y += spacing;
+    }
+    painter.add(mesh);
+}
+
+// MARK: - Level of detail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Detail {
+    // Outlined cells with the track drawn on them
+    Full,
+    // Plain filled cells, one mesh per group, once cells are too small to show track
+    Cells,
+}
+
+impl Detail {
+    pub fn of(camera: &Camera) -> Self {
+        if camera.cell_size() >= LOD_MIN_CELL_SIZE {
+            Detail::Full
+        } else {
+            Detail::Cells
+        }
+    }
+}
+
+// MARK: - Render style
+// How blocks are drawn: as cells with the track on top, or as schematic track of rails on
+// sleepers without the cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderStyle {
+    Cells,
+    Track,
+}
+
+impl RenderStyle {
+    pub const ALL: [RenderStyle; 2] = [RenderStyle::Cells, RenderStyle::Track];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RenderStyle::Cells => "Cells",
+            RenderStyle::Track => "Track",
+        }
+    }
+
+    pub fn toggled(self) -> Self {
+        match self {
+            RenderStyle::Cells => RenderStyle::Track,
+            RenderStyle::Track => RenderStyle::Cells,
+        }
+    }
+}
+
+// MARK: - Canvas cache
+// Prepared shapes of every group, positioned relative to the world origin on screen so panning
+// only has to move them. They are built for the nearest of a few zoom levels and scaled to the
+// exact zoom when painted, so zooming only rebuilds them once it passes the next level. A group's
+// shapes are rebuilt when the group changes, or all of them when the zoom level, level of detail,
+// render style or coloring changes; either way only once the group is in view.
+#[derive(Debug, Default)]
+pub struct CanvasCache {
+    groups: GroupCache<CachedGroup>,
+    style: Option<CanvasStyle>,
+}
+
+#[derive(Debug)]
+struct CachedGroup {
+    // Area the group may draw on, in world coordinates
+    bounds: egui::Rect,
+    // Each shape with the area it covers, for culling the parts of a group out of view
+    shapes: Option<Vec<(egui::Rect, egui::Shape)>>,
+}
+
+// Everything besides the group itself that its shapes depend on
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CanvasStyle {
+    zoom_level: i32,
+    detail: Detail,
+    render_style: RenderStyle,
+    endpoints_only: bool,
+}
+
+impl CanvasCache {
+    fn update(&mut self, layout: &Layout, history: &History, style: CanvasStyle) {
+        self.groups.sync(layout, history, |group| CachedGroup { bounds: group_bounds(group), shapes: None });
+        if self.style != Some(style) {
+            self.style = Some(style);
+            for (_, group) in self.groups.iter_mut() {
+                group.shapes = None;
+            }
+        }
+    }
+}
+
+// Cells of a group in world coordinates, with room for diagonal blocks reaching past them
+fn group_bounds(group: &Group) -> egui::Rect {
+    group.blocks.iter()
+        .map(|block| {
+            let min = egui::pos2(block.grid_pos.0 as f32 * GRID_SIZE, block.grid_pos.1 as f32 * GRID_SIZE);
+            egui::Rect::from_min_size(min, egui::Vec2::splat(GRID_SIZE))
+        })
+        .reduce(|a, b| a.union(b))
+        .map_or(egui::Rect::NOTHING, |bounds| bounds.expand(GRID_SIZE / 2.0))
+}
+
+fn group_shapes(group: &Group, camera: &Camera, style: CanvasStyle) -> Vec<(egui::Rect, egui::Shape)> {
+    match style.detail {
+        Detail::Full => group.blocks.iter()
+            .flat_map(|block| {
+                let state = BlockState::of(group, block.id, style.endpoints_only, false);
+                let center = camera.grid_to_screen(block.grid_pos);
+                let area = egui::Rect::from_center_size(center, egui::Vec2::splat(camera.cell_size() * 2.0));
+                styled_block_shapes(camera, block.grid_pos, block.kind, state, style.render_style).into_iter().map(move |shape| (area, shape))
+            })
+            .collect(),
+        Detail::Cells => {
+            let mut mesh = egui::Mesh::default();
+            let mut area = egui::Rect::NOTHING;
+            for block in &group.blocks {
+                let color = BlockState::of(group, block.id, style.endpoints_only, false).cell_color();
+                let rect = egui::Rect::from_center_size(camera.grid_to_screen(block.grid_pos), egui::Vec2::splat(camera.cell_size()));
+                mesh.add_colored_rect(rect, color);
+                area = area.union(rect);
+            }
+            vec![(area, egui::Shape::mesh(mesh))]
+        }
+    }
+}
+
+// How a block is colored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlockState {
+    Normal,
+    Selected,
+    // Selected group end while the Connect tool is active
+    SelectedEnd,
+    // Not a group end while the Connect tool is active
+    Disabled,
+}
+
+impl BlockState {
+    // The Connect tool only offers group ends, so it dims every other block
+    fn of(group: &Group, block_id: BlockId, endpoints_only: bool, selected: bool) -> Self {
+        if !endpoints_only {
+            return if selected { BlockState::Selected } else { BlockState::Normal };
+        }
+        match (group.start_block_id, group.end_block_id) {
+            (Some(start_id), Some(end_id)) if block_id != start_id && block_id != end_id => BlockState::Disabled,
+            _ if selected => BlockState::SelectedEnd,
+            _ => BlockState::Normal,
+        }
+    }
+
+    // Cells are green, red when selected and blue for selected ends
+    fn cell_color(self) -> Color32 {
+        match self {
+            BlockState::Normal => DEFAULT_BLOCK_COLOR,
+            BlockState::Selected => SELECTED_BLOCK_COLOR,
+            BlockState::SelectedEnd => SELECTED_CONNECTION_BLOCK_COLOR,
+            BlockState::Disabled => *DISABLED_BLOCK_COLOR,
+        }
+    }
+
+    // Schematic track has no cells, so the rails take the color instead
+    fn rail_color(self) -> Color32 {
+        match self {
+            BlockState::Normal => RAIL_COLOR,
+            BlockState::Selected => SELECTED_BLOCK_COLOR,
+            BlockState::SelectedEnd => SELECTED_CONNECTION_BLOCK_COLOR,
+            BlockState::Disabled => *DISABLED_RAIL_COLOR,
+        }
     }
 }
 
-pub fn draw_blocks(editor: &RailwayEditor, ui: &mut egui::Ui) {
+// MARK: - Blocks
+// Draws the groups in view from the canvas cache, then the selected blocks over them
+pub fn draw_blocks(editor: &mut RailwayEditor, ui: &mut egui::Ui) {
+    let RailwayEditor { layout, camera, tool, selected_blocks, history, canvas_cache, render_style, .. } = editor;
     let painter = ui.painter();
-    
-    // Draw blocks from all groups
-    for group in editor.groups.values() {
-        for block in &group.blocks {
-            let center = grid_to_screen(block.grid_pos);
-            let rect = egui::Rect::from_center_size(center, egui::vec2(BLOCK_SIZE, BLOCK_SIZE));
-            let mut color: Color32 = DEFAULT_BLOCK_COLOR;
-            
-            if editor.app_mode == AppMode::Normal {
-                color = if editor.selected_blocks.contains(&block.id) {
-                    SELECTED_BLOCK_COLOR
-                } else {
-                    DEFAULT_BLOCK_COLOR
-                };
-            } else if editor.app_mode == AppMode::SetConnections {
-                if let Some(end_id) = group.end_block_id {
-                    if let Some(start_id) = group.start_block_id {
-                        if block.id == end_id || block.id == start_id {
-                            color = if editor.selected_blocks.contains(&block.id) {
-                                SELECTED_CONNECTION_BLOCK_COLOR
-                            } else {
-                                DEFAULT_BLOCK_COLOR
-                            };
-                        } else {
-                            color =  *DISABLED_BLOCK_COLOR;
-                        };
-                    }
-                }
+    let zoom_level = (camera.zoom.log2() * CACHED_ZOOM_LEVELS).round() as i32;
+    let style = CanvasStyle {
+        zoom_level,
+        detail: Detail::of(camera),
+        render_style: *render_style,
+        endpoints_only: tool.endpoints_only(),
+    };
+    canvas_cache.update(layout, history, style);
+
+    // Shapes are cached at the zoom of their level and `factor` takes them to the current zoom
+    let local = Camera { zoom: (zoom_level as f32 / CACHED_ZOOM_LEVELS).exp2(), ..camera.at_origin() };
+    let factor = camera.zoom / local.zoom;
+    let origin = camera.world_to_screen(Pos2::ZERO).to_vec2();
+    let visible = camera.viewport.translate(-origin);
+    let visible = egui::Rect::from_min_max((visible.min.to_vec2() / factor).to_pos2(), (visible.max.to_vec2() / factor).to_pos2());
+    let visible_world = camera.visible_world_rect();
+    for (group_id, cached) in canvas_cache.groups.iter_mut() {
+        if !cached.bounds.intersects(visible_world) {
+            continue;
+        }
+        let Some(group) = layout.groups.get(&group_id) else {
+            continue;
+        };
+        let shapes = cached.shapes.get_or_insert_with(|| group_shapes(group, &local, style));
+        painter.extend(shapes.iter()
+            .filter(|(area, _)| area.intersects(visible))
+            .map(|(_, shape)| {
+                let mut shape = shape.clone();
+                scale_shape(&mut shape, factor);
+                shape.translate(origin);
+                shape
+            }));
+    }
+
+    for block_id in selected_blocks.iter() {
+        let (Some(group), Some(block)) = (layout.group_of_block(*block_id), layout.block(*block_id)) else {
+            continue;
+        };
+        let center = camera.grid_to_screen(block.grid_pos);
+        if !camera.viewport.expand(camera.cell_size()).contains(center) {
+            continue;
+        }
+        let state = BlockState::of(group, block.id, style.endpoints_only, true);
+        if state == BlockState::of(group, block.id, style.endpoints_only, false) {
+            continue;
+        }
+        match style.detail {
+            Detail::Full => painter.extend(styled_block_shapes(camera, block.grid_pos, block.kind, state, style.render_style)),
+            Detail::Cells => painter.rect_filled(egui::Rect::from_center_size(center, egui::Vec2::splat(camera.cell_size())), 0.0, state.cell_color()),
+        }
+    }
+}
+
+// Scales a shape about the screen origin, strokes and rounding included
+fn scale_shape(shape: &mut egui::Shape, factor: f32) {
+    let scale = |point: Pos2| (point.to_vec2() * factor).to_pos2();
+    match shape {
+        egui::Shape::Noop | egui::Shape::Text(_) | egui::Shape::Callback(_) => {}
+        egui::Shape::Vec(shapes) => {
+            for shape in shapes {
+                scale_shape(shape, factor);
             }
+        }
+        egui::Shape::Circle(circle) => {
+            circle.center = scale(circle.center);
+            circle.radius *= factor;
+            circle.stroke.width *= factor;
+        }
+        egui::Shape::LineSegment { points, stroke } => {
+            for point in points {
+                *point = scale(*point);
+            }
+            stroke.width *= factor;
+        }
+        egui::Shape::Path(path) => {
+            for point in &mut path.points {
+                *point = scale(*point);
+            }
+            path.stroke.width *= factor;
+        }
+        egui::Shape::Rect(rect) => {
+            rect.rect = egui::Rect::from_min_max(scale(rect.rect.min), scale(rect.rect.max));
+            let rounding = &mut rect.rounding;
+            for radius in [&mut rounding.nw, &mut rounding.ne, &mut rounding.sw, &mut rounding.se] {
+                *radius *= factor;
+            }
+            rect.stroke.width *= factor;
+        }
+        egui::Shape::Mesh(mesh) => {
+            for vertex in &mut mesh.vertices {
+                vertex.pos = scale(vertex.pos);
+            }
+        }
+        egui::Shape::QuadraticBezier(bezier) => {
+            for point in &mut bezier.points {
+                *point = scale(*point);
+            }
+            bezier.stroke.width *= factor;
+        }
+        egui::Shape::CubicBezier(bezier) => {
+            for point in &mut bezier.points {
+                *point = scale(*point);
+            }
+            bezier.stroke.width *= factor;
+        }
+    }
+}
+
+fn styled_block_shapes(camera: &Camera, grid_pos: (i32, i32), kind: BlockKind, state: BlockState, render_style: RenderStyle) -> Vec<egui::Shape> {
+    match render_style {
+        RenderStyle::Cells => block_shapes(camera, grid_pos, kind, state.cell_color()),
+        RenderStyle::Track => schematic::track_shapes(camera, camera.grid_to_screen(grid_pos), kind, state.rail_color()),
+    }
+}
+
+// A block's cell in the given color with its track on top. Diagonal straights are drawn along
+// their diagonal so consecutive blocks line up.
+fn block_shapes(camera: &Camera, grid_pos: (i32, i32), kind: BlockKind, color: Color32) -> Vec<egui::Shape> {
+    let center = camera.grid_to_screen(grid_pos);
+    let outline = egui::Stroke::new(1.0, Color32::BLACK);
+    let mut shapes = match kind {
+        BlockKind::Straight(direction) if direction.is_diagonal() => {
+            vec![egui::Shape::convex_polygon(diagonal_block_shape(camera, center, direction), color, outline)]
+        }
+        _ => {
+            let rect = egui::Rect::from_center_size(center, egui::Vec2::splat(camera.scale(BLOCK_SIZE)));
+            vec![egui::Shape::rect_filled(rect, camera.scale(BLOCK_ROUNDING), color), egui::Shape::rect_stroke(rect, camera.scale(BLOCK_ROUNDING), outline)]
+        }
+    };
+    shapes.extend(track_piece_shapes(camera, center, kind));
+    shapes
+}
+
+// Ghosts of the selected blocks at their position after moving by `offset`
+pub fn draw_move_preview(editor: &RailwayEditor, ui: &mut egui::Ui, offset: (i32, i32), valid: bool) {
+    let ghosts = editor.selected_blocks.iter()
+        .filter_map(|id| editor.layout.block(*id))
+        .map(|block| ((block.grid_pos.0 + offset.0, block.grid_pos.1 + offset.1), block.kind));
+    draw_ghost_blocks(ui.painter(), &editor.camera, ghosts, valid);
+}
+
+// Blocks that are about to be placed; red when they cannot be
+pub fn draw_ghost_blocks(painter: &egui::Painter, camera: &Camera, ghosts: impl Iterator<Item = ((i32, i32), BlockKind)>, valid: bool) {
+    let color = if valid { GHOST_BLOCK_COLOR } else { GHOST_BLOCK_BLOCKED_COLOR };
+    let visible = camera.viewport.expand(camera.cell_size());
 
-            painter.rect(rect, BLOCK_ROUNDING, color, egui::Stroke::new(1.0, egui::Color32::BLACK));
+    for (grid_pos, kind) in ghosts {
+        let center = camera.grid_to_screen(grid_pos);
+        if !visible.contains(center) {
+            continue;
         }
+        let rect = egui::Rect::from_center_size(center, egui::Vec2::splat(camera.scale(BLOCK_SIZE)));
+        painter.rect(rect, camera.scale(BLOCK_ROUNDING), color, egui::Stroke::new(1.0, Color32::WHITE));
+        painter.extend(track_piece_shapes(camera, center, kind));
     }
 }
 
+// Corners of a block rectangle turned onto the diagonal through its cell
+fn diagonal_block_shape(camera: &Camera, center: Pos2, direction: Direction) -> Vec<Pos2> {
+    let along = match direction {
+        Direction::DiagonalUp => egui::vec2(1.0, -1.0),
+        _ => egui::vec2(1.0, 1.0),
+    }.normalized();
+    let across = along.rot90();
+    let half_length = camera.scale(BLOCK_SIZE * std::f32::consts::FRAC_1_SQRT_2 + DIAGONAL_BLOCK_EXTRA);
+    let half_width = camera.scale(BLOCK_SIZE * DIAGONAL_BLOCK_WIDTH_RATIO / 2.0);
+    vec![
+        center - along * half_length - across * half_width,
+        center + along * half_length - across * half_width,
+        center + along * half_length + across * half_width,
+        center - along * half_length + across * half_width,
+    ]
+}
+
+// The track of a piece on top of its cell: one line per route through it
+pub fn track_piece_shapes(camera: &Camera, center: Pos2, kind: BlockKind) -> Vec<egui::Shape> {
+    let width = camera.scale(TRACK_STROKE_WIDTH).max(1.0);
+    let stroke = egui::Stroke::new(width, TRACK_COLOR);
+    let half = camera.scale(BLOCK_SIZE) / 2.0;
+    let port_point = |port: Port| {
+        let (dx, dy) = port.offset();
+        center + egui::vec2(dx as f32, dy as f32) * half
+    };
+
+    let mut shapes = vec![];
+    for (a, b) in kind.routes() {
+        if a.opposite() == b {
+            shapes.push(egui::Shape::line_segment([port_point(a), port_point(b)], stroke));
+        } else {
+            // Curved route, bent through the center of the cell
+            shapes.push(egui::epaint::QuadraticBezierShape::from_points_stroke(
+                [port_point(a), center, port_point(b)],
+                false,
+                Color32::TRANSPARENT,
+                stroke,
+            ).into());
+        }
+    }
+
+    if let BlockKind::BufferStop(port) = kind {
+        let track_dir = (port_point(port) - center).normalized();
+        let bar = track_dir.rot90() * half * BUFFER_STOP_BAR_RATIO;
+        shapes.push(egui::Shape::line_segment([port_point(port), center], stroke));
+        shapes.push(egui::Shape::line_segment([center - bar, center + bar], egui::Stroke::new(width * 1.5, TRACK_COLOR)));
+    }
+    shapes
+}
+
 pub fn draw_connections(editor: &RailwayEditor, ui: &mut egui::Ui) {
     let painter = ui.painter();
-    
-    // Draw connections between groups
-    for group in editor.groups.values() {
-        for connection in &group.connections {
-            if let Some(target_group) = editor.groups.get(&connection.to_group) {
-                if !group.blocks.is_empty() && !target_group.blocks.is_empty() {
-                    let start: Pos2;
-                    let end: Pos2;
-
-                    if connection.from_connection_type == ConnectionType::Start {
-                        start = grid_to_screen(group.blocks[0].grid_pos);
-                    } else {
-                        start = grid_to_screen(group.blocks.last().unwrap().grid_pos);
-                    }
-
-                    if connection.to_connection_type == ConnectionType::Start {
-                        end = grid_to_screen(target_group.blocks[0].grid_pos);
-                    } else {
-                        end = grid_to_screen(target_group.blocks.last().unwrap().grid_pos);
-                    }
-
-                    painter.line_segment(
-                        [start, end],
-                        egui::Stroke::new(ARROW_STROKE_WIDTH, CONNECTION_ARROW_COLOR),
-                    );
-                    
-                    // Draw arrow head
-                    let dir = (end - start).normalized();
-                    let arrow_size = ARROW_SIZE;
-                    let arrow_tip = end - dir * arrow_size;
-                    let arrow_left = arrow_tip + dir.rot90() * arrow_size * 0.5;
-                    let arrow_right = arrow_tip - dir.rot90() * arrow_size * 0.5;
-                    
-                    painter.add(egui::Shape::convex_polygon(
-                        vec![end, arrow_left, arrow_right],
-                        CONNECTION_ARROW_COLOR,
-                        egui::Stroke::NONE,
-                    ));
-                }
+
+    // Draw connections between group endpoints, with a clickable arrow halfway along.
+    // Zoomed out to plain cells the arrow heads are left out.
+    let detail = Detail::of(&editor.camera);
+    for connection in editor.layout.connections.iter() {
+        let Some((start, end)) = connection_line(&editor.layout, &editor.camera, connection) else {
+            continue;
+        };
+        if !segment_visible(&editor.camera, start, end) {
+            continue;
+        }
+        let selected = editor.selected_connection == Some(connection.id);
+        let (color, width) = if selected {
+            (SELECTED_CONNECTION_COLOR, SELECTED_ARROW_STROKE_WIDTH)
+        } else {
+            (CONNECTION_ARROW_COLOR, ARROW_STROKE_WIDTH)
+        };
+        painter.line_segment([start, end], egui::Stroke::new(width, color));
+        if start == end || detail == Detail::Cells {
+            continue;
+        }
+
+        let mid = start.lerp(end, 0.5);
+        let half_head = (end - start).normalized() * ARROW_SIZE * 0.5;
+        draw_arrow_head(painter, start, end, color);
+        draw_arrow_head(painter, start, mid + half_head, color);
+        if !connection.directed {
+            draw_arrow_head(painter, end, start, color);
+            draw_arrow_head(painter, end, mid - half_head, color);
+        }
+    }
+}
+
+// Dashed lines for the connections waiting in the Suggested Connections window
+pub fn draw_suggestions(editor: &RailwayEditor, ui: &mut egui::Ui) {
+    let painter = ui.painter();
+
+    for suggestion in &editor.suggestions {
+        let (Some(from), Some(to)) = (
+            editor.layout.endpoint_block(suggestion.from),
+            editor.layout.endpoint_block(suggestion.to),
+        ) else {
+            continue;
+        };
+        let color = if suggestion.picked { SUGGESTION_COLOR } else { SUGGESTION_UNPICKED_COLOR };
+        let points = [editor.camera.grid_to_screen(from.grid_pos), editor.camera.grid_to_screen(to.grid_pos)];
+        painter.extend(egui::Shape::dashed_line(
+            &points,
+            egui::Stroke::new(ARROW_STROKE_WIDTH, color),
+            SUGGESTION_DASH_LENGTH,
+            SUGGESTION_GAP_LENGTH,
+        ));
+    }
+}
+
+// Whether any of a line between two screen positions can be seen, arrow heads included
+fn segment_visible(camera: &Camera, start: Pos2, end: Pos2) -> bool {
+    egui::Rect::from_two_pos(start, end).expand(ARROW_SIZE).intersects(camera.viewport)
+}
+
+// Screen positions of the two end blocks of a connection
+pub fn connection_line(layout: &Layout, camera: &Camera, connection: &Connection) -> Option<(Pos2, Pos2)> {
+    let from = layout.endpoint_block(connection.from)?;
+    let to = layout.endpoint_block(connection.to)?;
+    Some((camera.grid_to_screen(from.grid_pos), camera.grid_to_screen(to.grid_pos)))
+}
+
+// Connection arrow; undirected ones get a head on both ends
+pub fn draw_arrow(painter: &egui::Painter, start: Pos2, end: Pos2, directed: bool) {
+    painter.line_segment(
+        [start, end],
+        egui::Stroke::new(ARROW_STROKE_WIDTH, CONNECTION_ARROW_COLOR),
+    );
+    if start == end {
+        return;
+    }
+    draw_arrow_head(painter, start, end, CONNECTION_ARROW_COLOR);
+    if !directed {
+        draw_arrow_head(painter, end, start, CONNECTION_ARROW_COLOR);
+    }
+}
+
+// Markers on group ends for the Connect tool: filled while free, hollow once attached
+pub fn draw_endpoint_markers(editor: &RailwayEditor, ui: &mut egui::Ui) {
+    let painter = ui.painter();
+
+    for group in editor.layout.groups.values() {
+        for end in [ConnectionType::Start, ConnectionType::End] {
+            let endpoint = Endpoint { group: group.id, end };
+            let Some(block) = editor.layout.endpoint_block(endpoint) else {
+                continue;
+            };
+            // A single block is both ends; draw it once
+            if end == ConnectionType::End && group.start_block_id == group.end_block_id {
+                continue;
+            }
+            let center = editor.camera.grid_to_screen(block.grid_pos);
+            if !editor.camera.viewport.expand(ENDPOINT_MARKER_RADIUS).contains(center) {
+                continue;
+            }
+            let stroke = egui::Stroke::new(ENDPOINT_MARKER_STROKE_WIDTH, ENDPOINT_MARKER_COLOR);
+            if editor.layout.attached_to(endpoint).is_some() {
+                painter.circle_stroke(center, ENDPOINT_MARKER_RADIUS, stroke);
+            } else {
+                painter.circle(center, ENDPOINT_MARKER_RADIUS, ENDPOINT_MARKER_COLOR, egui::Stroke::new(1.0, Color32::BLACK));
             }
         }
     }
-}
\ No newline at end of file
+}
+
+fn draw_arrow_head(painter: &egui::Painter, start: Pos2, end: Pos2, color: Color32) {
+    let dir = (end - start).normalized();
+    let arrow_tip = end - dir * ARROW_SIZE;
+    let arrow_left = arrow_tip + dir.rot90() * ARROW_SIZE * 0.5;
+    let arrow_right = arrow_tip - dir.rot90() * ARROW_SIZE * 0.5;
+
+    painter.add(egui::Shape::convex_polygon(
+        vec![end, arrow_left, arrow_right],
+        color,
+        egui::Stroke::NONE,
+    ));
+}
+
+// Turnout markers sit between the points endpoint and its two legs
+pub fn turnout_marker_pos(layout: &Layout, camera: &Camera, turnout: &Turnout) -> Option<Pos2> {
+    let points = camera.grid_to_screen(layout.endpoint_block(turnout.points)?.grid_pos);
+    let normal = camera.grid_to_screen(layout.endpoint_block(turnout.normal)?.grid_pos);
+    let reverse = camera.grid_to_screen(layout.endpoint_block(turnout.reverse)?.grid_pos);
+    let legs = normal.lerp(reverse, 0.5);
+    Some(points.lerp(legs, 0.5))
+}
+
+pub fn draw_turnouts(editor: &RailwayEditor, ui: &mut egui::Ui) {
+    let painter = ui.painter();
+
+    for turnout in editor.layout.turnouts.iter() {
+        let (Some(points), Some(active), Some(inactive), Some(marker)) = (
+            editor.layout.endpoint_block(turnout.points),
+            editor.layout.endpoint_block(turnout.active_leg()),
+            editor.layout.endpoint_block(turnout.inactive_leg()),
+            turnout_marker_pos(&editor.layout, &editor.camera, turnout),
+        ) else {
+            continue;
+        };
+        // The legs reach from the points cell to both leg ends, so the turnout is in view
+        // wherever any of these cells or the marker is
+        let cell = egui::Vec2::splat(editor.camera.cell_size());
+        let area = [points, active, inactive].into_iter()
+            .map(|block| egui::Rect::from_center_size(editor.camera.grid_to_screen(block.grid_pos), cell))
+            .fold(egui::Rect::from_center_size(marker, egui::Vec2::splat(TURNOUT_MARKER_RADIUS * 2.0)), |a, b| a.union(b));
+        if !editor.camera.viewport.intersects(area) {
+            continue;
+        }
+
+        let points = editor.camera.grid_to_screen(points.grid_pos);
+        painter.line_segment(
+            [points, editor.camera.grid_to_screen(inactive.grid_pos)],
+            egui::Stroke::new(TURNOUT_STROKE_WIDTH, TURNOUT_INACTIVE_COLOR),
+        );
+        painter.line_segment(
+            [points, editor.camera.grid_to_screen(active.grid_pos)],
+            egui::Stroke::new(TURNOUT_STROKE_WIDTH, TURNOUT_ACTIVE_COLOR),
+        );
+        painter.circle(marker, TURNOUT_MARKER_RADIUS, TURNOUT_ACTIVE_COLOR, egui::Stroke::new(1.0, Color32::BLACK));
+    }
+}
@@ -0,0 +1,174 @@
+use eframe::egui;
+use egui::{Color32, Pos2, Shape, Vec2};
+use railway_layout::{BlockKind, Port};
+use crate::camera::Camera;
+use crate::utils::*;
+
+// MARK: - Schematic track
+// The "track" style: every route through a block is drawn as a pair of rails on sleepers.
+// Routes run from the edge of the cell to the edge, so consecutive blocks join without a seam,
+// and routes that turn are arcs tangent to both of their ports.
+
+// Rails, sleepers and glyphs of one block. Sleepers and glyphs fade along with the rails.
+pub fn track_shapes(camera: &Camera, center: Pos2, kind: BlockKind, rail_color: Color32) -> Vec<Shape> {
+    let half = camera.cell_size() / 2.0;
+    let opacity = rail_color.a() as f32 / 255.0;
+    let mut centerlines: Vec<Vec<Pos2>> = kind.routes().into_iter()
+        .map(|(a, b)| route_centerline(center, half, a, b))
+        .collect();
+    if let BlockKind::BufferStop(port) = kind {
+        centerlines.push(vec![port_point(center, half, port), center]);
+    }
+
+    let mut shapes = vec![];
+    // Sleepers first so every rail lies on top of them
+    for line in &centerlines {
+        shapes.extend(sleepers(camera, line, SLEEPER_COLOR.linear_multiply(opacity)));
+    }
+    for line in &centerlines {
+        shapes.extend(rails(camera, line, rail_color));
+    }
+
+    match kind {
+        BlockKind::BufferStop(port) => shapes.extend(buffer_stop_glyph(camera, center, half, port, BUFFER_STOP_COLOR.linear_multiply(opacity))),
+        BlockKind::Turnout { points, .. } => {
+            let legs: Vec<Port> = kind.routes().into_iter().map(|(_, leg)| leg).collect();
+            shapes.push(turnout_glyph(center, half, points, &legs, TURNOUT_GLYPH_COLOR.linear_multiply(opacity)));
+        }
+        _ => {}
+    }
+    shapes
+}
+
+// Where a port meets the edge or corner of the cell
+fn port_point(center: Pos2, half: f32, port: Port) -> Pos2 {
+    let (dx, dy) = port.offset();
+    center + egui::vec2(dx as f32, dy as f32) * half
+}
+
+// Centerline of the route between two ports, from edge to edge. Both port directions pass
+// through the center of the cell, so a turning route is the arc tangent to them there, with a
+// straight lead-in on the side that reaches further.
+fn route_centerline(center: Pos2, half: f32, a: Port, b: Port) -> Vec<Pos2> {
+    let start = port_point(center, half, a);
+    let end = port_point(center, half, b);
+    let heading_in = (center - start).normalized();
+    let heading_out = (end - center).normalized();
+    if heading_in.dot(heading_out) > 0.999 {
+        return vec![start, end];
+    }
+
+    let tangent = start.distance(center).min(end.distance(center));
+    let arc_start = center - heading_in * tangent;
+    let arc_end = center + heading_out * tangent;
+    let turn = heading_in.dot(heading_out).clamp(-1.0, 1.0).acos();
+    let radius = tangent / (turn / 2.0).tan();
+    // The arc bends towards the way out
+    let mut normal = heading_in.rot90();
+    if normal.dot(heading_out) < 0.0 {
+        normal = -normal;
+    }
+    let arc_center = arc_start + normal * radius;
+
+    let from = (arc_start - arc_center).angle();
+    let mut sweep = (arc_end - arc_center).angle() - from;
+    if sweep > std::f32::consts::PI {
+        sweep -= std::f32::consts::TAU;
+    } else if sweep < -std::f32::consts::PI {
+        sweep += std::f32::consts::TAU;
+    }
+
+    let mut points = vec![start];
+    for i in 0..=TRACK_ARC_SEGMENTS {
+        let angle = from + sweep * i as f32 / TRACK_ARC_SEGMENTS as f32;
+        points.push(arc_center + Vec2::angled(angle) * radius);
+    }
+    points.push(end);
+    points.dedup_by(|a, b| a.distance(*b) < 0.01);
+    points
+}
+
+// Unit normal of a polyline at each of its points
+fn normals(line: &[Pos2]) -> Vec<Vec2> {
+    (0..line.len())
+        .map(|i| {
+            let before = line[i.saturating_sub(1)];
+            let after = line[(i + 1).min(line.len() - 1)];
+            (after - before).normalized().rot90()
+        })
+        .collect()
+}
+
+fn rails(camera: &Camera, line: &[Pos2], color: Color32) -> [Shape; 2] {
+    let offset = camera.scale(RAIL_GAUGE) / 2.0;
+    let stroke = egui::Stroke::new(camera.scale(RAIL_STROKE_WIDTH).max(1.0), color);
+    let normals = normals(line);
+    let rail = |side: f32| {
+        let points = line.iter().zip(&normals).map(|(point, normal)| *point + *normal * offset * side).collect();
+        Shape::line(points, stroke)
+    };
+    [rail(1.0), rail(-1.0)]
+}
+
+// Sleepers evenly spread along the line, across it
+fn sleepers(camera: &Camera, line: &[Pos2], color: Color32) -> Vec<Shape> {
+    let lengths: Vec<f32> = line.windows(2).map(|pair| pair[0].distance(pair[1])).collect();
+    let total: f32 = lengths.iter().sum();
+    if total <= 0.0 {
+        return vec![];
+    }
+    let count = (total / camera.scale(SLEEPER_SPACING)).round().max(1.0) as usize;
+    let half_length = camera.scale(SLEEPER_LENGTH) / 2.0;
+    let stroke = egui::Stroke::new(camera.scale(SLEEPER_STROKE_WIDTH).max(1.0), color);
+
+    let mut shapes = Vec::with_capacity(count);
+    let (mut segment, mut walked) = (0, 0.0);
+    for i in 0..count {
+        let at = (i as f32 + 0.5) * total / count as f32;
+        while segment + 1 < lengths.len() && walked + lengths[segment] < at {
+            walked += lengths[segment];
+            segment += 1;
+        }
+        let (a, b) = (line[segment], line[segment + 1]);
+        let point = a.lerp(b, ((at - walked) / lengths[segment]).clamp(0.0, 1.0));
+        let across = (b - a).normalized().rot90() * half_length;
+        shapes.push(Shape::line_segment([point - across, point + across], stroke));
+    }
+    shapes
+}
+
+// A beam across the end of the track with the stop block behind it
+fn buffer_stop_glyph(camera: &Camera, center: Pos2, half: f32, port: Port, color: Color32) -> [Shape; 2] {
+    let inward = (center - port_point(center, half, port)).normalized();
+    let across = inward.rot90() * camera.scale(RAIL_GAUGE);
+    let depth = inward * camera.scale(BUFFER_STOP_DEPTH);
+    let beam = Shape::line_segment(
+        [center - across, center + across],
+        egui::Stroke::new(camera.scale(RAIL_STROKE_WIDTH * 2.0).max(1.5), color),
+    );
+    let block = filled_polygon(
+        vec![center - across * 0.6, center + across * 0.6, center + across * 0.6 + depth, center - across * 0.6 + depth],
+        color,
+    );
+    [beam, block]
+}
+
+// A wedge between the legs, pointing back at the switch blades
+fn turnout_glyph(center: Pos2, half: f32, points: Port, legs: &[Port], color: Color32) -> Shape {
+    let tip = port_point(center, half, points).lerp(center, TURNOUT_GLYPH_TIP);
+    let mut corners = vec![tip];
+    corners.extend(legs.iter().map(|leg| center.lerp(port_point(center, half, *leg), TURNOUT_GLYPH_SPREAD)));
+    filled_polygon(corners, color)
+}
+
+// Convex polygon in the clockwise order egui expects, whichever way the glyph faces
+fn filled_polygon(mut corners: Vec<Pos2>, color: Color32) -> Shape {
+    let area: f32 = corners.iter()
+        .zip(corners.iter().cycle().skip(1))
+        .map(|(a, b)| a.x * b.y - b.x * a.y)
+        .sum();
+    if area < 0.0 {
+        corners.reverse();
+    }
+    Shape::convex_polygon(corners, color, egui::Stroke::NONE)
+}
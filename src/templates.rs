@@ -0,0 +1,96 @@
+use railway_layout::{BlockId, BlockKind, Direction, Fragment, Layout, LayoutError, Port};
+
+// MARK: - Template
+// A ready-made piece of layout offered from the canvas context menu
+pub struct Template {
+    pub name: &'static str,
+    build: fn(&mut Layout) -> Result<(), LayoutError>,
+}
+
+impl Template {
+    // The template laid out on its own, with its top-left corner at (0, 0)
+    pub fn fragment(&self) -> Result<Fragment, LayoutError> {
+        let mut layout = Layout::default();
+        (self.build)(&mut layout)?;
+        let blocks: Vec<BlockId> = layout.groups.values()
+            .flat_map(|group| group.blocks.iter().map(|block| block.id))
+            .collect();
+        layout.copy_fragment(&blocks)
+    }
+}
+
+pub const TEMPLATES: [Template; 3] = [
+    Template { name: "Straight run", build: straight_run },
+    Template { name: "Siding with buffer stops", build: siding },
+    Template { name: "Oval", build: oval },
+];
+
+// MARK: - Builders
+fn straight_run(layout: &mut Layout) -> Result<(), LayoutError> {
+    layout.lay_run((0, 0), (5, 0))?;
+    Ok(())
+}
+
+fn siding(layout: &mut Layout) -> Result<(), LayoutError> {
+    layout.place_block((0, 0), BlockKind::BufferStop(Port::East))?;
+    layout.lay_run((1, 0), (4, 0))?;
+    layout.place_block((5, 0), BlockKind::BufferStop(Port::West))?;
+    Ok(())
+}
+
+// A closed loop of 8 by 4 cells with a curve in each corner
+fn oval(layout: &mut Layout) -> Result<(), LayoutError> {
+    let (right, bottom) = (7, 3);
+    layout.place_block((0, 0), BlockKind::Curve(Port::East))?;
+    layout.place_block((right, 0), BlockKind::Curve(Port::South))?;
+    layout.place_block((right, bottom), BlockKind::Curve(Port::West))?;
+    layout.place_block((0, bottom), BlockKind::Curve(Port::North))?;
+    for x in 1..right {
+        layout.place_block((x, 0), BlockKind::Straight(Direction::Horizontal))?;
+        layout.place_block((x, bottom), BlockKind::Straight(Direction::Horizontal))?;
+    }
+    for y in 1..bottom {
+        layout.place_block((0, y), BlockKind::Straight(Direction::Vertical))?;
+        layout.place_block((right, y), BlockKind::Straight(Direction::Vertical))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Paste the named template into an empty layout, as placing it from the context menu does
+    fn placed(name: &str) -> Layout {
+        let template = TEMPLATES.iter().find(|template| template.name == name).unwrap();
+        let mut layout = Layout::default();
+        layout.paste_fragment(&template.fragment().unwrap(), (0, 0)).unwrap();
+        assert!(layout.validate().is_empty());
+        layout
+    }
+
+    #[test]
+    fn straight_run_is_one_group() {
+        let layout = placed("Straight run");
+        assert_eq!((layout.groups.len(), layout.block_count()), (1, 6));
+    }
+
+    #[test]
+    fn siding_ends_in_buffer_stops() {
+        let layout = placed("Siding with buffer stops");
+        assert_eq!((layout.groups.len(), layout.block_count()), (1, 6));
+        let group = layout.groups.values().next().unwrap();
+        assert!(matches!(group.blocks.first().unwrap().kind, BlockKind::BufferStop(_)));
+        assert!(matches!(group.blocks.last().unwrap().kind, BlockKind::BufferStop(_)));
+    }
+
+    #[test]
+    fn oval_closes_into_one_loop() {
+        let layout = placed("Oval");
+        assert_eq!((layout.groups.len(), layout.block_count()), (1, 20));
+        // The ends of a closed loop are next to each other
+        let group = layout.groups.values().next().unwrap();
+        let (start, end) = (group.blocks.first().unwrap().grid_pos, group.blocks.last().unwrap().grid_pos);
+        assert_eq!((start.0 - end.0).abs() + (start.1 - end.1).abs(), 1);
+    }
+}
@@ -0,0 +1,554 @@
+use eframe::egui;
+use crate::editor::{RailwayEditor, SelectionMode};
+use crate::rendering::{connection_line, draw_arrow, draw_ghost_blocks, draw_move_preview, turnout_marker_pos};
+use crate::utils::*;
+use railway_layout::{BlockId, BlockKind, ConnectionId, Layout, LayoutError, TurnoutId};
+
+// MARK: - Tool
+// The active tool decides what pointer input on the canvas does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Select,
+    Place,
+    Line,
+    Erase,
+    Connect,
+    Pan,
+    Measure,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 7] = [Tool::Select, Tool::Place, Tool::Line, Tool::Erase, Tool::Connect, Tool::Pan, Tool::Measure];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Tool::Select => "Select",
+            Tool::Place => "Place",
+            Tool::Line => "Line",
+            Tool::Erase => "Erase",
+            Tool::Connect => "Connect",
+            Tool::Pan => "Pan",
+            Tool::Measure => "Measure",
+        }
+    }
+
+    pub fn hotkey(&self) -> egui::Key {
+        match self {
+            Tool::Select => egui::Key::V,
+            Tool::Place => egui::Key::P,
+            Tool::Line => egui::Key::L,
+            Tool::Erase => egui::Key::E,
+            Tool::Connect => egui::Key::C,
+            Tool::Pan => egui::Key::H,
+            Tool::Measure => egui::Key::M,
+        }
+    }
+
+    // What the tool does, for the status bar and the palette tooltips
+    pub fn hint(&self) -> &'static str {
+        match self {
+            Tool::Select => "Click to select, Shift-click to add, double-click for the group. Drag the selection to move it or the canvas to select an area.",
+            Tool::Place => "Click to place the chosen track piece. R rotates it.",
+            Tool::Line => "Drag to lay a straight run of track.",
+            Tool::Erase => "Click or drag over blocks to remove them.",
+            Tool::Connect => "Drag from one group end to another to connect them. Add Turnout picks three ends with Shift-click instead.",
+            Tool::Pan => "Drag to move the view.",
+            Tool::Measure => "Drag between two cells to measure the distance.",
+        }
+    }
+
+    pub fn cursor(&self) -> egui::CursorIcon {
+        match self {
+            Tool::Select => egui::CursorIcon::Default,
+            Tool::Place | Tool::Line => egui::CursorIcon::Cell,
+            Tool::Erase => egui::CursorIcon::NotAllowed,
+            Tool::Connect => egui::CursorIcon::PointingHand,
+            Tool::Pan => egui::CursorIcon::Grab,
+            Tool::Measure => egui::CursorIcon::Crosshair,
+        }
+    }
+
+    // Tools in which only group start and end blocks can be selected
+    pub fn endpoints_only(&self) -> bool {
+        matches!(self, Tool::Connect)
+    }
+}
+
+// MARK: - ConnectMode
+// What the Connect tool builds from the selected endpoints
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectMode {
+    Connections,
+    Turnouts,
+}
+
+// MARK: - Switching tools
+impl RailwayEditor {
+    pub fn set_tool(&mut self, tool: Tool) {
+        if tool == self.tool {
+            return;
+        }
+        // A selection made for one tool means something else to the other
+        if tool.endpoints_only() != self.tool.endpoints_only() {
+            self.selected_blocks.clear();
+        }
+        self.tool = tool;
+        self.connect_mode = ConnectMode::Connections;
+        self.marquee_start = None;
+        self.move_start = None;
+        self.line_start = None;
+        self.erasing.clear();
+        self.measure_start = None;
+        self.connect_from = None;
+    }
+
+    // Single-key shortcuts for the tools; ignored while typing or with Ctrl held
+    pub fn handle_tool_hotkeys(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() || ctx.input(|i| i.modifiers.command) {
+            return;
+        }
+        if let Some(tool) = Tool::ALL.into_iter().find(|tool| ctx.input(|i| i.key_pressed(tool.hotkey()))) {
+            self.set_tool(tool);
+        }
+    }
+}
+
+// MARK: - Canvas input
+impl RailwayEditor {
+    // Hand the canvas input to the active tool. Runs after the layout is drawn so
+    // previews end up on top.
+    pub fn handle_tool_input(&mut self, ui: &mut egui::Ui, response: &egui::Response) {
+        if self.handle_view_input(ui, response) {
+            return;
+        }
+        if response.hovered() {
+            ui.ctx().set_cursor_icon(self.tool.cursor());
+        }
+
+        match self.tool {
+            Tool::Select => {
+                self.handle_clicks(ui, response);
+                self.handle_move_drag(ui, response);
+                self.handle_marquee(ui, response);
+            }
+            Tool::Place => self.handle_place(ui, response),
+            Tool::Line => self.handle_line_drag(ui, response),
+            Tool::Erase => self.handle_erase(ui, response),
+            Tool::Connect => match self.connect_mode {
+                ConnectMode::Connections => {
+                    self.handle_clicks(ui, response);
+                    self.handle_connect_drag(ui, response);
+                }
+                ConnectMode::Turnouts => self.handle_turnout_clicks(ui, response),
+            },
+            // Panning is handled with the rest of the view input
+            Tool::Pan => {}
+            Tool::Measure => self.handle_measure(ui, response),
+        }
+    }
+
+    // What is under a screen position. Turnout markers and connection arrows lie on top
+    // of blocks; a connection's line only counts where there is no block.
+    pub fn hit_test(&self, pos: egui::Pos2) -> Option<Hit> {
+        let turnout = self.layout.turnouts.iter().find(|turnout| {
+            turnout_marker_pos(&self.layout, &self.camera, turnout)
+                .is_some_and(|center| center.distance(pos) <= TURNOUT_MARKER_RADIUS)
+        });
+        if let Some(turnout) = turnout {
+            return Some(Hit::Turnout(turnout.id));
+        }
+
+        let arrow = self.layout.connections.iter().find(|connection| {
+            connection_line(&self.layout, &self.camera, connection).is_some_and(|(start, end)| {
+                egui::Rect::from_center_size(start.lerp(end, 0.5), egui::Vec2::splat(ARROW_SIZE * 2.0)).contains(pos)
+            })
+        });
+        if let Some(connection) = arrow {
+            return Some(Hit::Connection(connection.id));
+        }
+
+        self.block_under(pos).map(Hit::Block)
+            .or_else(|| self.connection_at(pos).map(Hit::Connection))
+    }
+
+    // Block under a screen position
+    fn block_under(&self, pos: egui::Pos2) -> Option<BlockId> {
+        self.layout.block_at(self.camera.screen_to_grid(pos)).map(|(_, block)| block.id)
+    }
+
+    // Connection whose line passes closest to a screen position, within reach
+    fn connection_at(&self, pos: egui::Pos2) -> Option<ConnectionId> {
+        self.layout.connections.iter()
+            .filter_map(|connection| {
+                let (start, end) = connection_line(&self.layout, &self.camera, connection)?;
+                let distance = distance_to_segment(pos, start, end);
+                (distance <= CONNECTION_HIT_DISTANCE).then_some((distance, connection.id))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, id)| id)
+    }
+}
+
+// MARK: - Hit
+// What lies under the pointer on the canvas
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hit {
+    Turnout(TurnoutId),
+    Connection(ConnectionId),
+    Block(BlockId),
+}
+
+// MARK: - Select tool
+impl RailwayEditor {
+    // Clicking a turnout marker throws it and clicking a connection selects it.
+    // A click on a block selects it, Shift-click toggles it and double-click selects its group;
+    // with the Connect tool only group ends can be picked. Clicking empty canvas clears the selection.
+    fn handle_clicks(&mut self, ui: &mut egui::Ui, response: &egui::Response) {
+        match response.hover_pos().and_then(|pos| self.hit_test(pos)) {
+            Some(Hit::Turnout(id)) => {
+                if let Some(turnout) = self.layout.turnouts.get(id) {
+                    response.clone().on_hover_text_at_pointer(format!("Turnout {}: {:?}", turnout.id, turnout.position));
+                }
+            }
+            Some(Hit::Connection(id)) => {
+                if let Some(connection) = self.layout.connections.get(id) {
+                    response.clone().on_hover_text_at_pointer(format!("Connection {}: {} to {}", connection.id, connection.from, connection.to));
+                }
+            }
+            _ => {}
+        }
+
+        let shift = ui.input(|i| i.modifiers.shift);
+        if response.clicked() {
+            match response.interact_pointer_pos().and_then(|pos| self.hit_test(pos)) {
+                Some(Hit::Turnout(id)) => self.toggle_turnout(id),
+                Some(Hit::Connection(id)) => {
+                    self.selected_connection = Some(id);
+                    self.selected_blocks.clear();
+                }
+                Some(Hit::Block(id)) => self.click_block(id, shift),
+                None if !shift => {
+                    self.selected_blocks.clear();
+                    self.selected_connection = None;
+                }
+                None => {}
+            }
+        }
+
+        // Double click selects the whole group
+        if response.double_clicked() && !self.tool.endpoints_only() {
+            if let Some(Hit::Block(id)) = response.interact_pointer_pos().and_then(|pos| self.hit_test(pos)) {
+                let mode = if shift { SelectionMode::Add } else { SelectionMode::Replace };
+                self.select_group(id, mode);
+            }
+        }
+    }
+
+    fn click_block(&mut self, block_id: BlockId, shift: bool) {
+        self.selected_connection = None;
+        if shift && self.selected_blocks.contains(&block_id) {
+            self.selected_blocks.shift_remove(&block_id);
+            return;
+        }
+        if !shift {
+            self.selected_blocks.clear();
+        }
+
+        let Some(group) = self.layout.group_of_block(block_id) else {
+            return;
+        };
+        if self.tool.endpoints_only() && !group.is_endpoint(block_id) {
+            self.show_message("Cannot select the block");
+            return;
+        }
+        self.selected_blocks.insert(block_id);
+    }
+
+    // Dragging a selected block moves the whole selection, previewed as ghosts
+    // that turn red where the move would overlap other blocks
+    fn handle_move_drag(&mut self, ui: &mut egui::Ui, response: &egui::Response) {
+        if response.drag_started() {
+            let origin = ui.input(|i| i.pointer.press_origin());
+            let on_selection = origin
+                .and_then(|origin| self.block_under(origin))
+                .is_some_and(|id| self.selected_blocks.contains(&id));
+            self.move_start = if on_selection { origin } else { None };
+        }
+        if !response.dragged() && !response.drag_released() {
+            self.move_start = None;
+        }
+        let Some(start) = self.move_start else {
+            return;
+        };
+
+        let current = ui.input(|i| i.pointer.interact_pos()).unwrap_or(start);
+        let delta = (current - start) / self.camera.cell_size();
+        let offset = (delta.x.round() as i32, delta.y.round() as i32);
+        let selected: Vec<_> = self.selected_blocks.iter().copied().collect();
+        let valid = self.layout.can_move_blocks(&selected, offset).is_ok();
+        draw_move_preview(self, ui, offset, valid);
+
+        if response.drag_released() {
+            self.move_start = None;
+            if valid {
+                self.move_selected_blocks(offset);
+            }
+        }
+    }
+
+    // Dragging on the canvas selects every block the rectangle touches.
+    // Shift adds to the selection and Ctrl removes from it.
+    fn handle_marquee(&mut self, ui: &mut egui::Ui, response: &egui::Response) {
+        if response.drag_started() && self.move_start.is_none() {
+            self.marquee_start = ui.input(|i| i.pointer.press_origin());
+        }
+        if !response.dragged() && !response.drag_released() {
+            self.marquee_start = None;
+        }
+        let Some(start) = self.marquee_start else {
+            return;
+        };
+        let current = ui.input(|i| i.pointer.interact_pos()).unwrap_or(start);
+        let marquee = egui::Rect::from_two_pos(start, current);
+        ui.painter().rect(marquee, 0.0, MARQUEE_FILL_COLOR, egui::Stroke::new(1.0, MARQUEE_STROKE_COLOR));
+
+        if response.drag_released() {
+            self.marquee_start = None;
+            let mode = ui.input(|i| {
+                if i.modifiers.shift {
+                    SelectionMode::Add
+                } else if i.modifiers.command {
+                    SelectionMode::Subtract
+                } else {
+                    SelectionMode::Replace
+                }
+            });
+            let touched = self.blocks_touched_by(marquee);
+            self.select_blocks(touched, mode);
+        }
+    }
+}
+
+impl RailwayEditor {
+    // Blocks whose square overlaps a screen rectangle. Small rectangles look up the cells they
+    // cover; ones spanning more cells than there are blocks check every block instead.
+    fn blocks_touched_by(&self, rect: egui::Rect) -> Vec<BlockId> {
+        let touches = |grid_pos: (i32, i32)| {
+            let block_rect = egui::Rect::from_center_size(self.camera.grid_to_screen(grid_pos), egui::Vec2::splat(self.camera.scale(BLOCK_SIZE)));
+            rect.intersects(block_rect)
+        };
+        let (min, max) = (self.camera.screen_to_grid(rect.min), self.camera.screen_to_grid(rect.max));
+        let cells = (max.0 - min.0 + 1) as usize * (max.1 - min.1 + 1) as usize;
+        if cells > self.layout.block_count() {
+            return self.layout.groups.values()
+                .flat_map(|group| group.blocks.iter())
+                .filter(|block| touches(block.grid_pos))
+                .map(|block| block.id)
+                .collect();
+        }
+        (min.1..=max.1)
+            .flat_map(|y| (min.0..=max.0).map(move |x| (x, y)))
+            .filter_map(|cell| self.layout.block_at(cell))
+            .filter(|(_, block)| touches(block.grid_pos))
+            .map(|(_, block)| block.id)
+            .collect()
+    }
+}
+
+// MARK: - Place tool
+impl RailwayEditor {
+    // A ghost of the piece follows the pointer; clicking places it
+    fn handle_place(&mut self, ui: &mut egui::Ui, response: &egui::Response) {
+        let Some(hover) = response.hover_pos() else {
+            return;
+        };
+        let cell = self.camera.screen_to_grid(hover);
+        let kind = self.placing_kind.unwrap_or_else(|| self.layout.auto_kind(cell));
+        let valid = !self.layout.is_occupied(cell);
+        draw_ghost_blocks(ui.painter(), &self.camera, std::iter::once((cell, kind)), valid);
+
+        if response.clicked() {
+            self.place_block(cell);
+        }
+    }
+}
+
+// MARK: - Line tool
+impl RailwayEditor {
+    // Press on a cell and drag to preview a straight run; releasing lays it.
+    // A plain click places a single block.
+    fn handle_line_drag(&mut self, ui: &mut egui::Ui, response: &egui::Response) {
+        if response.clicked() {
+            if let Some(pos) = response.interact_pointer_pos() {
+                let cell = self.camera.screen_to_grid(pos);
+                self.lay_run(cell, cell);
+            }
+        }
+
+        if response.drag_started() {
+            self.line_start = ui.input(|i| i.pointer.press_origin()).map(|pos| self.camera.screen_to_grid(pos));
+        }
+        if !response.dragged() && !response.drag_released() {
+            self.line_start = None;
+        }
+        let Some(start) = self.line_start else {
+            return;
+        };
+
+        let end = ui.input(|i| i.pointer.interact_pos()).map_or(start, |pos| self.camera.screen_to_grid(pos));
+        let (direction, cells) = Layout::straight_run(start, end);
+        let valid = !cells.iter().any(|cell| self.layout.is_occupied(*cell));
+        let ghosts = cells.iter().map(|cell| (*cell, BlockKind::Straight(direction)));
+        draw_ghost_blocks(ui.painter(), &self.camera, ghosts, valid);
+
+        if response.drag_released() {
+            self.line_start = None;
+            self.lay_run(start, end);
+        }
+    }
+}
+
+// MARK: - Erase tool
+impl RailwayEditor {
+    // Blocks swept by a drag are marked and removed together on release, as one step
+    fn handle_erase(&mut self, ui: &mut egui::Ui, response: &egui::Response) {
+        if response.clicked() {
+            if let Some(block) = response.interact_pointer_pos().and_then(|pos| self.block_under(pos)) {
+                self.erase_blocks(vec![block]);
+            }
+        }
+
+        if response.dragged() {
+            let swept = ui.input(|i| i.pointer.interact_pos()).and_then(|pos| self.block_under(pos));
+            if let Some(block) = swept.filter(|block| !self.erasing.contains(block)) {
+                self.erasing.push(block);
+            }
+        }
+
+        let hovered = response.hover_pos().and_then(|pos| self.block_under(pos));
+        let marked = self.erasing.iter().copied().chain(hovered)
+            .filter_map(|id| self.layout.block(id))
+            .map(|block| (block.grid_pos, block.kind));
+        draw_ghost_blocks(ui.painter(), &self.camera, marked, false);
+
+        if response.drag_released() {
+            let erasing = std::mem::take(&mut self.erasing);
+            if !erasing.is_empty() {
+                self.erase_blocks(erasing);
+            }
+        }
+    }
+}
+
+// MARK: - Connect tool
+impl RailwayEditor {
+    // The points, normal leg and reverse leg are picked in that order with Shift-click. Picking
+    // the third end does not build anything yet: clicking the reverse leg once more adds the turnout.
+    fn handle_turnout_clicks(&mut self, ui: &mut egui::Ui, response: &egui::Response) {
+        let shift = ui.input(|i| i.modifiers.shift);
+        let reverse = (self.selected_blocks.len() == 3).then(|| self.selected_blocks[2]);
+        let confirmed = response.clicked()
+            && !shift
+            && reverse.is_some()
+            && response.interact_pointer_pos().and_then(|pos| self.hit_test(pos)) == reverse.map(Hit::Block);
+        if confirmed {
+            self.add_turnout();
+        } else {
+            self.handle_clicks(ui, response);
+        }
+    }
+
+    // Press on a group end and drag an arrow to another end; releasing there connects them.
+    // Blocks that cannot take the connection turn red with the reason as a tooltip.
+    fn handle_connect_drag(&mut self, ui: &mut egui::Ui, response: &egui::Response) {
+        if response.drag_started() {
+            self.connect_from = ui.input(|i| i.pointer.press_origin())
+                .and_then(|pos| self.block_under(pos))
+                .and_then(|block| self.layout.endpoint_of_block(block).ok());
+        }
+        if !response.dragged() && !response.drag_released() {
+            self.connect_from = None;
+        }
+
+        let pointer = ui.input(|i| i.pointer.interact_pos()).or(response.hover_pos());
+        let target = pointer.and_then(|pos| self.block_under(pos)).map(|block| {
+            let endpoint = self.layout.endpoint_of_block(block);
+            let checked = match self.connect_from {
+                Some(from) => endpoint.and_then(|to| self.layout.can_connect(from, to).map(|_| to)),
+                // Before a drag, only say whether a connection can start here
+                None => endpoint.and_then(|end| match self.layout.attached_to(end) {
+                    Some(_) => Err(LayoutError::EndpointInUse(end)),
+                    None => Ok(end),
+                }),
+            };
+            (block, checked)
+        });
+
+        if let Some((block, Err(reason))) = &target {
+            if let Some(block) = self.layout.block(*block) {
+                draw_ghost_blocks(ui.painter(), &self.camera, std::iter::once((block.grid_pos, block.kind)), false);
+            }
+            egui::show_tooltip_at_pointer(ui.ctx(), egui::Id::new("connect_rejected"), |ui| {
+                ui.label(reason.to_string());
+            });
+        }
+
+        let Some(from) = self.connect_from else {
+            return;
+        };
+        let Some(start) = self.layout.endpoint_block(from).map(|block| self.camera.grid_to_screen(block.grid_pos)) else {
+            return;
+        };
+        let to = match target {
+            Some((_, Ok(to))) => Some(to),
+            _ => None,
+        };
+        // The arrow snaps onto a target that would be accepted
+        let end = to.and_then(|to| self.layout.endpoint_block(to))
+            .map(|block| self.camera.grid_to_screen(block.grid_pos))
+            .or(pointer)
+            .unwrap_or(start);
+        draw_arrow(ui.painter(), start, end, !self.connect_bidirectional);
+
+        if response.drag_released() {
+            self.connect_from = None;
+            if let Some(to) = to {
+                self.connect(from, to);
+            }
+        }
+    }
+}
+
+// MARK: - Measure tool
+impl RailwayEditor {
+    // While dragging, show the offset and straight-line distance between two cells
+    fn handle_measure(&mut self, ui: &mut egui::Ui, response: &egui::Response) {
+        if response.drag_started() {
+            self.measure_start = ui.input(|i| i.pointer.press_origin()).map(|pos| self.camera.screen_to_grid(pos));
+        }
+        if !response.dragged() {
+            self.measure_start = None;
+        }
+        let Some(start) = self.measure_start else {
+            return;
+        };
+        let end = ui.input(|i| i.pointer.interact_pos()).map_or(start, |pos| self.camera.screen_to_grid(pos));
+
+        let painter = ui.painter();
+        let (from, to) = (self.camera.grid_to_screen(start), self.camera.grid_to_screen(end));
+        let stroke = egui::Stroke::new(MEASURE_STROKE_WIDTH, MEASURE_COLOR);
+        painter.line_segment([from, to], stroke);
+        painter.circle_stroke(from, MEASURE_MARKER_RADIUS, stroke);
+        painter.circle_stroke(to, MEASURE_MARKER_RADIUS, stroke);
+
+        let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+        let distance = ((dx * dx + dy * dy) as f32).sqrt();
+        painter.text(
+            to + egui::vec2(MEASURE_MARKER_RADIUS, -MEASURE_MARKER_RADIUS),
+            egui::Align2::LEFT_BOTTOM,
+            format!("Δx {}, Δy {} ({:.1} cells)", dx, dy, distance),
+            egui::FontId::proportional(14.0),
+            MEASURE_COLOR,
+        );
+    }
+}
@@ -11,36 +11,116 @@ pub const BLOCK_SIZE: f32 = 40.0;
 pub const GRID_LINE_COLOR:Color32 = egui::Color32::from_gray(50);
 pub const GRID_LINE_STROKE_WIDTH:f32 = 1.0;
 
+// Camera
+pub const MIN_ZOOM:f32 = 0.05;
+pub const MAX_ZOOM:f32 = 4.0;
+// Zoom factor per point of scrolling
+pub const ZOOM_SCROLL_SPEED:f32 = 0.002;
+// Empty cells kept around the layout by Zoom to Fit
+pub const ZOOM_TO_FIT_MARGIN:f32 = 1.0;
+// Closest grid lines may get on screen; zoomed out further only every `GRID_LOD_STEP`th line is drawn
+pub const MIN_GRID_LINE_SPACING:f32 = 6.0;
+pub const GRID_LOD_STEP:f32 = 5.0;
+// Below this cell size on screen blocks are drawn as plain cells without track
+pub const LOD_MIN_CELL_SIZE:f32 = 12.0;
+// Cached canvas shapes are built for this many zoom levels per doubling and scaled to the exact zoom
+pub const CACHED_ZOOM_LEVELS:f32 = 4.0;
+
 
 
 // Block
 pub const DEFAULT_BLOCK_COLOR:Color32 = egui::Color32::from_rgb(100, 200, 100);
 pub const SELECTED_BLOCK_COLOR:Color32 = egui::Color32::from_rgb(200, 100, 100);
 pub const SELECTED_CONNECTION_BLOCK_COLOR:Color32 = Color32::from_rgb(100, 100, 200);
-pub const DISABLED_BLOCK_COLOR:Lazy<Color32> = Lazy::new(|| egui::Color32::from_rgba_unmultiplied(100, 200, 100, 50));
+pub static DISABLED_BLOCK_COLOR:Lazy<Color32> = Lazy::new(|| egui::Color32::from_rgba_unmultiplied(100, 200, 100, 50));
 
 pub const BLOCK_ROUNDING: f32 = 2.0;
+pub const DIAGONAL_BLOCK_WIDTH_RATIO: f32 = 0.6;
+pub const DIAGONAL_BLOCK_EXTRA: f32 = 4.0;
+
+// Track glyph drawn on a block
+pub const TRACK_COLOR:Color32 = egui::Color32::from_gray(30);
+pub const TRACK_STROKE_WIDTH:f32 = 3.0;
+pub const BUFFER_STOP_BAR_RATIO:f32 = 0.6;
+
+// Schematic track style: rails on sleepers, with glyphs for buffer stops and turnouts
+pub const RAIL_COLOR:Color32 = egui::Color32::from_gray(200);
+pub static DISABLED_RAIL_COLOR:Lazy<Color32> = Lazy::new(|| egui::Color32::from_rgba_unmultiplied(200, 200, 200, 50));
+pub const RAIL_GAUGE:f32 = 10.0;
+pub const RAIL_STROKE_WIDTH:f32 = 2.0;
+pub const SLEEPER_COLOR:Color32 = egui::Color32::from_rgb(110, 80, 50);
+pub const SLEEPER_SPACING:f32 = 12.5;
+pub const SLEEPER_LENGTH:f32 = 18.0;
+pub const SLEEPER_STROKE_WIDTH:f32 = 3.0;
+// Straight pieces a curved rail is made of
+pub const TRACK_ARC_SEGMENTS:usize = 8;
+pub const BUFFER_STOP_COLOR:Color32 = egui::Color32::from_rgb(200, 60, 40);
+pub const BUFFER_STOP_DEPTH:f32 = 6.0;
+pub const TURNOUT_GLYPH_COLOR:Color32 = egui::Color32::from_rgba_premultiplied(150, 104, 0, 150);
+// Where the wedge's tip sits between the points side and the center, and how far out its legs reach
+pub const TURNOUT_GLYPH_TIP:f32 = 0.6;
+pub const TURNOUT_GLYPH_SPREAD:f32 = 0.5;
+
+// Ghost blocks previewing a move or a run of track
+pub const GHOST_BLOCK_COLOR:Color32 = egui::Color32::from_rgba_premultiplied(60, 120, 60, 120);
+pub const GHOST_BLOCK_BLOCKED_COLOR:Color32 = egui::Color32::from_rgba_premultiplied(140, 30, 30, 120);
+
+// Marquee selection
+pub const MARQUEE_FILL_COLOR:Color32 = egui::Color32::from_rgba_premultiplied(40, 60, 100, 40);
+pub const MARQUEE_STROKE_COLOR:Color32 = egui::Color32::from_rgb(120, 160, 255);
+
+// Measure tool
+pub const MEASURE_COLOR:Color32 = egui::Color32::from_rgb(255, 220, 120);
+pub const MEASURE_STROKE_WIDTH:f32 = 2.0;
+pub const MEASURE_MARKER_RADIUS:f32 = 6.0;
+
+// Turnout
+pub const TURNOUT_ACTIVE_COLOR:Color32 = egui::Color32::from_rgb(230, 160, 0);
+pub const TURNOUT_INACTIVE_COLOR:Color32 = egui::Color32::from_rgba_premultiplied(80, 60, 0, 80);
+pub const TURNOUT_STROKE_WIDTH:f32 = 3.0;
+pub const TURNOUT_MARKER_RADIUS:f32 = 7.0;
 
 // Connection Arrow
 pub const CONNECTION_ARROW_COLOR:Color32 = egui::Color32::from_rgb(0, 0, 255);
 pub const ARROW_STROKE_WIDTH:f32 = 2.0;
 pub const ARROW_SIZE:f32 = 10.0;
+pub const SELECTED_CONNECTION_COLOR:Color32 = egui::Color32::from_rgb(255, 140, 0);
+pub const SELECTED_ARROW_STROKE_WIDTH:f32 = 4.0;
+// How far from its line a click still picks a connection
+pub const CONNECTION_HIT_DISTANCE:f32 = 6.0;
 
+// Suggested connections awaiting review
+pub const SUGGESTION_COLOR:Color32 = egui::Color32::from_rgb(0, 200, 220);
+pub const SUGGESTION_UNPICKED_COLOR:Color32 = egui::Color32::from_rgba_premultiplied(0, 80, 90, 120);
+pub const SUGGESTION_DASH_LENGTH:f32 = 6.0;
+pub const SUGGESTION_GAP_LENGTH:f32 = 4.0;
 
+// Group end markers shown by the Connect tool
+pub const ENDPOINT_MARKER_COLOR:Color32 = egui::Color32::from_rgb(240, 240, 240);
+pub const ENDPOINT_MARKER_RADIUS:f32 = 6.0;
+pub const ENDPOINT_MARKER_STROKE_WIDTH:f32 = 2.0;
 
-pub fn snap_to_grid(pos: egui::Pos2) -> (i32, i32) {
-    (
-        (pos.x / GRID_SIZE).floor() as i32,
-        (pos.y / GRID_SIZE).floor() as i32,
-    )
-}
+// Minimap
+pub const MINIMAP_SIZE:egui::Vec2 = egui::vec2(200.0, 150.0);
+pub const MINIMAP_BACKGROUND_COLOR:Color32 = egui::Color32::from_gray(20);
+pub const MINIMAP_VIEWPORT_COLOR:Color32 = egui::Color32::from_rgb(240, 240, 240);
+pub const MINIMAP_VIEWPORT_STROKE_WIDTH:f32 = 1.5;
+pub const MINIMAP_CONNECTION_STROKE_WIDTH:f32 = 1.0;
+
+// Problems
+pub const PROBLEM_ERROR_COLOR:Color32 = egui::Color32::from_rgb(220, 60, 60);
+pub const PROBLEM_WARNING_COLOR:Color32 = egui::Color32::from_rgb(220, 170, 0);
+
+// History
+pub const HISTORY_REDO_COLOR:Color32 = egui::Color32::GRAY;
 
-pub fn grid_to_screen(pos: (i32, i32)) -> egui::Pos2 {
-    egui::pos2(
-         // Even the box needs some tempo(7.5) to find its place in the center
-        (pos.0 as f32 * GRID_SIZE) + (GRID_SIZE / 2.0) + 7.5, // Center horizontally
-        (pos.1 as f32 * GRID_SIZE) + (GRID_SIZE / 2.0) + 7.5, // Center vertically
-    )
+
+
+// Shortest distance from `pos` to the segment from `a` to `b`
+pub fn distance_to_segment(pos: egui::Pos2, a: egui::Pos2, b: egui::Pos2) -> f32 {
+    let ab = b - a;
+    let t = if ab.length_sq() > 0.0 { ((pos - a).dot(ab) / ab.length_sq()).clamp(0.0, 1.0) } else { 0.0 };
+    pos.distance(a + ab * t)
 }
 
 
@@ -51,4 +131,27 @@ pub const APP_NAME:&str = "Railway Layout Editor";
 
 // Message Box
 pub const MESSAGE_BOX_TITLE:&str = "Notification";
-pub const MESSAGE_BOX_BUTTON_TEXT:&str = "Ok";
\ No newline at end of file
+pub const MESSAGE_BOX_BUTTON_TEXT:&str = "Ok";
+
+// Tool palette
+pub const TOOL_PALETTE_TITLE:&str = "Tools";
+
+// Connection window
+pub const CONNECTION_PANEL_TITLE:&str = "Connection";
+
+// Suggestions panel
+pub const SUGGESTIONS_PANEL_TITLE:&str = "Suggested Connections";
+pub const SUGGESTIONS_LIST_HEIGHT:f32 = 240.0;
+
+// Group windows
+pub const RENAME_WINDOW_TITLE:&str = "Rename Group";
+pub const GROUP_PROPERTIES_TITLE:&str = "Group Properties";
+
+// Problems panel
+pub const PROBLEMS_PANEL_TITLE:&str = "Problems";
+
+// History panel
+pub const HISTORY_PANEL_TITLE:&str = "History";
+
+// Minimap window
+pub const MINIMAP_TITLE:&str = "Minimap";